@@ -26,6 +26,7 @@ fn main() {
             in_place(&mut data, &Cfg {
                 minify_js: false,
                 minify_css: false,
+                ..Cfg::default()
             }).unwrap();
         };
         let elapsed = start.elapsed().as_secs_f64();
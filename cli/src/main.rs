@@ -1,9 +1,10 @@
 use std::fs::File;
 use std::io::{Read, stdin, stdout, Write};
+use std::sync::Arc;
 
 use structopt::StructOpt;
 
-use minify_html::{Cfg, FriendlyError, with_friendly_error};
+use minify_html::{Cfg, FriendlyError, MinifyCache, Selector, with_friendly_error};
 
 #[derive(StructOpt)]
 #[structopt(name = "minify-html", about = "Extremely fast and smart HTML + JS + CSS minifier")]
@@ -20,6 +21,21 @@ struct Cli {
     /// Enables CSS minification.
     #[structopt(long)]
     css: bool,
+    /// Enables JSON minification.
+    #[structopt(long)]
+    json: bool,
+    /// Keeps all comments.
+    #[structopt(long)]
+    keep_comments: bool,
+    /// Attribute to remove from all elements; end with `*` to remove all attributes with the prefix. Can be repeated.
+    #[structopt(long = "remove-attr")]
+    remove_attributes: Vec<String>,
+    /// Selector, like `template`, `.ad`, or `div[data-role=test]`, of elements to remove with their content. Can be repeated.
+    #[structopt(long = "remove-element")]
+    remove_elements: Vec<Selector>,
+    /// Directory to cache minified JS, CSS, and JSON in, to reuse between runs.
+    #[structopt(long, parse(from_os_str))]
+    cache_dir: Option<std::path::PathBuf>,
 }
 
 macro_rules! io_expect {
@@ -43,9 +59,19 @@ fn main() {
         None => Box::new(stdin()),
     };
     io_expect!(src_file.read_to_end(&mut code), "could not load source code");
+    let cache = match args.cache_dir {
+        Some(dir) => Some(Arc::new(io_expect!(MinifyCache::with_dir(dir), "could not create cache directory"))),
+        None => None,
+    };
     match with_friendly_error(&mut code, &Cfg {
         minify_js: args.js,
         minify_css: args.css,
+        minify_json: args.json,
+        keep_comments: args.keep_comments,
+        remove_attributes: args.remove_attributes.into_iter().map(|a| a.to_ascii_lowercase().into_bytes()).collect(),
+        remove_elements: args.remove_elements,
+        cache,
+        ..Cfg::default()
     }) {
         Ok(out_len) => {
             let mut out_file: Box<dyn Write> = match args.out {
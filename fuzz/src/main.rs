@@ -7,6 +7,7 @@ fn main() {
         let _ = in_place(&mut mut_data, &Cfg {
             minify_js: false,
             minify_css: false,
+            ..Cfg::default()
         });
     });
 }
@@ -6,6 +6,7 @@ pub extern "C" fn ffi_create_cfg(minify_js: bool, minify_css: bool) -> *const Cf
     Box::into_raw(Box::new(Cfg {
         minify_js,
         minify_css,
+        ..Cfg::default()
     }))
 }
 
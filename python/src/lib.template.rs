@@ -10,6 +10,7 @@ fn minify(code: String, minify_js: bool, minify_css: bool) -> PyResult<String> {
     match minify_html_native(&mut code, &Cfg {
         minify_js,
         minify_css,
+        ..Cfg::default()
     }) {
         Ok(out_len) => Ok(unsafe { from_utf8_unchecked(&code[0..out_len]).to_string() }),
         Err(Error { error_type, position }) => Err(PySyntaxError::new_err(format!("{} [Character {}]", error_type.message(), position))),
@@ -28,6 +28,7 @@ methods! {
                 .at(&Symbol::new("minify_css"))
                 .try_convert_to::<Boolean>()
                 .map_or(false, |v| v.to_bool()),
+            ..Cfg::default()
         };
 
         minify_html_native(&mut code, cfg)
@@ -1,17 +1,151 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::cfg::selector::Selector;
+use crate::hook::Hook;
+use crate::minifier::{BuiltinJsonMinifier, CodeKind, ContentMinifier, default_minifier, MinifyCache, MinifyResult};
+use crate::spec::tag::whitespace::WhitespaceGroup;
+
+pub mod selector;
+
+/// What to do when a section of JS, CSS, or JSON code can't be minified, such as when it's invalid.
+/// The original code is always kept.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum MinifyFailurePolicy {
+    /// Keep the original code without reporting anything.
+    #[default]
+    Ignore,
+    /// Record an `ErrorType::MinifyFailed` warning, which is returned by `in_place_with_warnings`,
+    /// `in_place_lenient`, and `diagnose`.
+    Warn,
+    /// Fail minification with an `ErrorType::MinifyFailed` error.
+    Fail,
+}
+
 /// Configuration settings that can be adjusted and passed to a minification function to change the
 /// minification approach.
+#[derive(Clone, Default)]
 pub struct Cfg {
-    /// If enabled, JavaScript in `<script>` tags are minified using
-    /// [esbuild-rs](https://github.com/wilsonzlin/esbuild-rs). The `js-esbuild` feature must be
-    /// enabled; otherwise, this value has no effect.
+    /// If enabled, JavaScript in `<script>` tags and event handler attributes is minified using
+    /// `minifier`.
     ///
     /// Only `<script>` tags with a valid or no
-    /// [MIME type](https://mimesniff.spec.whatwg.org/#javascript-mime-type) is considered to
-    /// contain JavaScript, as per the specification.
+    /// [MIME type](https://mimesniff.spec.whatwg.org/#javascript-mime-type), a `type` of
+    /// `module`, or a `type` in `js_mime_types` are considered to contain JavaScript.
     pub minify_js: bool,
 
-    /// If enabled, CSS in `<style>` tags are minified using
-    /// [esbuild-rs](https://github.com/wilsonzlin/esbuild-rs). The `js-esbuild` feature must be
-    /// enabled; otherwise, this value has no effect.
+    /// Values of the `type` attribute on `<script>` tags, in addition to the
+    /// [JavaScript MIME types](https://mimesniff.spec.whatwg.org/#javascript-mime-type), that
+    /// indicate the tag contains JavaScript to minify. Unlike JavaScript MIME types, these are not
+    /// removed. `<script type="module">` is always considered to contain JavaScript.
+    pub js_mime_types: Vec<Vec<u8>>,
+
+    /// If enabled, CSS in `<style>` tags and `style` attributes is minified using `minifier`.
     pub minify_css: bool,
+
+    /// If enabled, JSON in `<script>` tags with a `type` of `application/json`,
+    /// `application/ld+json`, `importmap`, or `speculationrules` is minified using `minifier`. The
+    /// original JSON is kept if it's not valid.
+    pub minify_json: bool,
+
+    /// Minifier to use for JS, CSS, and JSON. If not set, `EsbuildMinifier` is used if the
+    /// `js-esbuild` feature is enabled, or `BuiltinCssMinifier` (which only minifies CSS) if the
+    /// `css-builtin` feature is enabled; otherwise, `minify_js` and `minify_css` have no effect.
+    /// JSON is minified using `BuiltinJsonMinifier` if this is not set. To change how esbuild
+    /// minifies code, such as its target, use `EsbuildMinifier::with_options`.
+    pub minifier: Option<Arc<dyn ContentMinifier>>,
+
+    /// What to do when `minifier` fails to minify some code. The error's position is where the
+    /// section of code starts in the source, and it contains the minifier's messages. By default,
+    /// failures are ignored.
+    pub on_minify_failure: MinifyFailurePolicy,
+
+    /// Cache of minified JS, CSS, and JSON code to use, so that code that appears many times, such
+    /// as across many documents, is only minified once. Use `MinifyCache::with_dir` to also reuse
+    /// minified code between runs.
+    pub cache: Option<Arc<MinifyCache>>,
+
+    /// If enabled, `<script>` content is parsed according to the
+    /// [script data escaped states](https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-state),
+    /// where a `</script>` after a `<script` in an HTML comment inside the script does not end it.
+    /// This is a legacy feature and is slower to parse, so it's disabled by default. JavaScript
+    /// containing an HTML comment is not minified when enabled.
+    pub parse_escaped_script_data: bool,
+
+    /// If enabled, all comments are kept instead of removed. Kept comments are written as is.
+    pub keep_comments: bool,
+
+    /// If enabled, comments starting with `<!--!`, commonly used for license and copyright notices,
+    /// are kept even if `keep_comments` is not enabled.
+    pub keep_license_comments: bool,
+
+    /// If enabled, server-side include directives like `<!--#include virtual="/footer.html" -->`
+    /// are kept even if `keep_comments` is not enabled.
+    pub keep_ssi_comments: bool,
+
+    /// Comments whose content starts with any of these prefixes are kept even if `keep_comments`
+    /// is not enabled. For example, the prefix `b" htmlmin:keep"` will keep
+    /// `<!-- htmlmin:keep -->`.
+    pub keep_comment_prefixes: Vec<Vec<u8>>,
+
+    /// Pairs of opening and closing delimiters of template syntax, such as `(b"{{", b"}}")` or
+    /// `(b"<%", b"%>")`. Templates are copied as is in content, tag names, attribute names, and
+    /// attribute values, and an attribute value containing a template is not minified at all.
+    pub template_delimiters: Vec<(Vec<u8>, Vec<u8>)>,
+
+    /// Whitespace groups of elements by lowercase tag name, such as custom elements used for
+    /// layout (e.g. `b"x-card"` as `WhitespaceGroup::Layout`), which add to or override the
+    /// built-in groups. Elements not in any group are `WhitespaceGroup::Other`.
+    pub whitespace_groups: HashMap<Vec<u8>, WhitespaceGroup>,
+
+    /// If enabled, whitespace is kept as is in elements, and all their descendants, that have a
+    /// `style` attribute setting `white-space` to `pre`, `pre-wrap`, `pre-line`, or
+    /// `break-spaces`, or that are `contenteditable`, like in `<pre>`. Only inline styles are
+    /// considered.
+    pub infer_whitespace_sensitivity: bool,
+
+    /// Names of attributes to remove from all elements, such as `b"data-testid"`. A name ending in
+    /// `*` removes all attributes starting with it, such as `b"ng-reflect-*"`.
+    pub remove_attributes: Vec<Vec<u8>>,
+
+    /// Elements matching any of these selectors are removed along with all their content.
+    pub remove_elements: Vec<Selector>,
+
+    /// Callbacks to drop or replace elements and attributes while minifying. Elements and
+    /// attributes removed by `remove_elements` and `remove_attributes` are not passed to it.
+    pub hook: Option<Arc<dyn Hook>>,
+}
+
+impl Cfg {
+    // Whether an attribute with this (lowercase) name should be removed according to `remove_attributes`.
+    pub(crate) fn is_removed_attribute(&self, name: &[u8]) -> bool {
+        self.remove_attributes.iter().any(|r| match r.split_last() {
+            Some((b'*', prefix)) => name.starts_with(prefix),
+            _ => r.as_slice() == name,
+        })
+    }
+
+    // The minifier to use for JS and CSS, if any.
+    pub(crate) fn content_minifier(&self) -> Option<&dyn ContentMinifier> {
+        match &self.minifier {
+            Some(minifier) => Some(minifier.as_ref()),
+            None => default_minifier(),
+        }
+    }
+
+    // Minify code using `minifier` and the cache, if any.
+    pub(crate) fn minify_async(&self, minifier: &dyn ContentMinifier, kind: CodeKind, code: Vec<u8>, done: Box<dyn FnOnce(MinifyResult) + Send>) -> () {
+        match &self.cache {
+            Some(cache) => MinifyCache::minify_async(cache, minifier, kind, code, done),
+            None => minifier.minify_async(kind, code, done),
+        };
+    }
+
+    // The minifier to use for JSON.
+    pub(crate) fn json_minifier(&self) -> &dyn ContentMinifier {
+        match &self.minifier {
+            Some(minifier) => minifier.as_ref(),
+            None => &BuiltinJsonMinifier,
+        }
+    }
 }
@@ -0,0 +1,125 @@
+use std::str::FromStr;
+
+use crate::gen::codepoints::{ATTR_NAME_CHAR, ATTR_QUOTE, TAG_NAME_CHAR, WHITESPACE};
+use crate::proc::entity::decode_entities;
+use crate::tokenizer::StartTag;
+
+/// A simple CSS selector that matches elements by tag name, classes, ID, and attributes, like
+/// `template`, `[hidden]`, `.ad`, `#banner`, `div[data-role=test]`, or
+/// `script[type="text/x-template"]`. Either the tag name or the rest can be omitted, and `*`
+/// matches any tag name. Tag and attribute names are case insensitive; classes, IDs, and
+/// attribute values are not.
+///
+/// Only a single compound selector is supported, so combinators (e.g. `div p` or `ul > li`),
+/// pseudo-classes (e.g. `:first-child`), selector lists (e.g. `a, b`), escapes, and attribute
+/// operators other than `=` (e.g. `[class~=a]`) are not, and parsing them fails.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Selector {
+    // Lowercase tag name, or None if any.
+    name: Option<Vec<u8>>,
+    // Lowercase attribute names, and what their values must be.
+    attributes: Vec<(Vec<u8>, AttrValue)>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum AttrValue {
+    Any,
+    Equals(Vec<u8>),
+    // A whitespace separated list containing the word, like `class`.
+    Includes(Vec<u8>),
+}
+
+impl Selector {
+    /// Parse a selector, returning None if it's not valid.
+    pub fn parse(selector: &str) -> Option<Selector> {
+        let code = selector.as_bytes();
+        let universal = code.starts_with(b"*");
+        let mut pos = if universal {
+            1
+        } else {
+            code.iter().position(|c| !TAG_NAME_CHAR[*c]).unwrap_or(code.len())
+        };
+        let name = if universal || pos == 0 { None } else { Some(code[..pos].to_ascii_lowercase()) };
+        let mut attributes = Vec::new();
+        while pos < code.len() {
+            if code[pos] == b'.' || code[pos] == b'#' {
+                // Classes and IDs end at the next part of the selector.
+                let start = pos + 1;
+                let end = start + code[start..].iter().position(|c| b".#[]:>+~,()*".contains(c) || WHITESPACE[*c]).unwrap_or(code.len() - start);
+                if end == start {
+                    return None;
+                };
+                let value = code[start..end].to_vec();
+                attributes.push(if code[pos] == b'.' {
+                    (b"class".to_vec(), AttrValue::Includes(value))
+                } else {
+                    (b"id".to_vec(), AttrValue::Equals(value))
+                });
+                pos = end;
+                continue;
+            };
+            if code[pos] != b'[' {
+                return None;
+            };
+            pos += 1;
+            // Characters used by other attribute operators, like `~=`, aren't part of the name, so they're not valid.
+            let name_end = pos + code[pos..].iter().position(|c| !ATTR_NAME_CHAR[*c] || b"]~|^$*".contains(c)).unwrap_or(code.len() - pos);
+            if name_end == pos {
+                return None;
+            };
+            let attr_name = code[pos..name_end].to_ascii_lowercase();
+            pos = name_end;
+            let value = if code.get(pos) == Some(&b'=') {
+                pos += 1;
+                let start = match code.get(pos) {
+                    Some(&quote) if ATTR_QUOTE[quote] => pos + 1,
+                    _ => pos,
+                };
+                // Quoted values end at the closing quote, and unquoted values at the `]`.
+                let end = match code.get(pos) {
+                    Some(&quote) if ATTR_QUOTE[quote] => start + code[start..].iter().position(|c| *c == quote)?,
+                    _ => start + code[start..].iter().position(|c| *c == b']').unwrap_or(code.len() - start),
+                };
+                pos = end + (start > pos) as usize;
+                AttrValue::Equals(code[start..end].to_vec())
+            } else {
+                AttrValue::Any
+            };
+            if code.get(pos) != Some(&b']') {
+                return None;
+            };
+            pos += 1;
+            attributes.push((attr_name, value));
+        };
+        if !universal && name.is_none() && attributes.is_empty() {
+            return None;
+        };
+        Some(Selector { name, attributes })
+    }
+
+    // Whether the element started by `tag` matches this selector.
+    pub(crate) fn matches(&self, tag: &StartTag) -> bool {
+        if self.name.as_ref().filter(|n| !tag.name.bytes.eq_ignore_ascii_case(n)).is_some() {
+            return false;
+        };
+        self.attributes.iter().all(|(name, expected)| tag.attributes.iter().any(|attr| {
+            if !attr.name.bytes.eq_ignore_ascii_case(name) {
+                return false;
+            };
+            let value = attr.value.as_ref().map_or(Vec::new(), |v| decode_entities(v.bytes, true));
+            match expected {
+                AttrValue::Any => true,
+                AttrValue::Equals(expected) => value == *expected,
+                AttrValue::Includes(word) => value.split(|c| WHITESPACE[*c]).any(|w| w == word.as_slice()),
+            }
+        }))
+    }
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(selector: &str) -> Result<Selector, String> {
+        Selector::parse(selector).ok_or_else(|| format!("Invalid selector \"{}\".", selector))
+    }
+}
@@ -0,0 +1,49 @@
+use crate::err::{Error, ErrorType};
+
+/// How severe a problem found in the source code is.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Severity {
+    /// The problem could not be recovered from, so minification would fail.
+    Error,
+    /// The problem was recovered from the same way browsers do, but the code is not valid.
+    Warning,
+}
+
+/// A problem found in the source code, with its location in a few forms for use in editors and other tools.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub error_type: ErrorType,
+    pub severity: Severity,
+    /// Line number, starting from 1.
+    pub line: usize,
+    /// Column number in Unicode characters, starting from 1.
+    pub column: usize,
+    /// Offset in bytes from the start of the source code.
+    pub position: usize,
+    /// Offset in UTF-16 code units from the start of the source code.
+    pub utf16_position: usize,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(code: &[u8], Error { error_type, position }: Error, severity: Severity) -> Diagnostic {
+        let position = position.min(code.len());
+        let mut line = 1;
+        let mut column = 1;
+        let mut utf16_position = 0;
+        for &c in &code[..position] {
+            // Ignore UTF-8 continuation bytes.
+            if c & 0xc0 == 0x80 {
+                continue;
+            };
+            if c == b'\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            };
+            // Characters outside the BMP (four byte UTF-8 sequences) are surrogate pairs in UTF-16.
+            utf16_position += if c >= 0xf0 { 2 } else { 1 };
+        };
+        Diagnostic { error_type, severity, line, column, position, utf16_position }
+    }
+}
@@ -1,32 +1,91 @@
+use std::fmt;
+
+use crate::minifier::CodeKind;
+
 /// Represents the type of minification error.
 #[derive(Debug, Eq, PartialEq)]
 pub enum ErrorType {
     ClosingTagMismatch { expected: String, got: String },
-    NotFound(&'static str),
+    /// Something that was `expected` was not found in a `construct` (e.g. "comment") that started at position `start`.
+    NotFound { expected: &'static str, construct: &'static str, start: usize },
     UnexpectedEnd,
     UnexpectedClosingTag,
+    DuplicateAttribute(String),
+    InvalidEntity,
+    VoidElementClosingTag(String),
+    UnclosedElement(String),
+    /// A section of `kind` code that ends at position `end` couldn't be minified, for the reasons in `messages`.
+    MinifyFailed { kind: CodeKind, end: usize, messages: Vec<String> },
+    /// A `Hook` replaced code with something longer than the source code it replaces.
+    ReplacementTooLong,
 }
 
 impl ErrorType {
     /// Generates an English message describing the error with any additional context.
-    pub fn message(self) -> String {
+    pub fn message(&self) -> String {
+        self.to_string()
+    }
+
+    /// A stable machine-readable code identifying the type of error, which won't change between versions.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorType::ClosingTagMismatch { .. } => "closing-tag-mismatch",
+            ErrorType::NotFound { .. } => "not-found",
+            ErrorType::UnexpectedEnd => "unexpected-end",
+            ErrorType::UnexpectedClosingTag => "unexpected-closing-tag",
+            ErrorType::DuplicateAttribute(_) => "duplicate-attribute",
+            ErrorType::InvalidEntity => "invalid-entity",
+            ErrorType::VoidElementClosingTag(_) => "void-element-closing-tag",
+            ErrorType::UnclosedElement(_) => "unclosed-element",
+            ErrorType::MinifyFailed { .. } => "minify-failed",
+            ErrorType::ReplacementTooLong => "replacement-too-long",
+        }
+    }
+}
+
+impl fmt::Display for ErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ErrorType::ClosingTagMismatch { expected, got } => {
-                format!("Closing tag name does not match opening tag (expected \"{}\", got \"{}\").", expected, got)
+                write!(f, "Closing tag name does not match opening tag (expected \"{}\", got \"{}\").", expected, got)
             }
-            ErrorType::NotFound(exp) => {
-                format!("Expected {}.", exp)
+            ErrorType::NotFound { expected, construct, start } => {
+                write!(f, "Expected {} for {} starting at position {}.", expected, construct, start)
             }
             ErrorType::UnexpectedEnd => {
-                format!("Unexpected end of source code.")
+                write!(f, "Unexpected end of source code.")
             }
             ErrorType::UnexpectedClosingTag => {
-                format!("Unexpected closing tag.")
+                write!(f, "Unexpected closing tag.")
+            }
+            ErrorType::DuplicateAttribute(name) => {
+                write!(f, "Duplicate attribute \"{}\".", name)
+            }
+            ErrorType::InvalidEntity => {
+                write!(f, "Invalid numeric entity, which decodes to U+FFFD.")
+            }
+            ErrorType::VoidElementClosingTag(name) => {
+                write!(f, "Closing tag for void element \"{}\".", name)
+            }
+            ErrorType::UnclosedElement(name) => {
+                write!(f, "Element \"{}\" is not closed.", name)
+            }
+            ErrorType::MinifyFailed { kind, end, messages } => {
+                if messages.is_empty() {
+                    write!(f, "Could not minify {} code ending at position {}.", kind, end)
+                } else {
+                    write!(f, "Could not minify {} code ending at position {}: {}", kind, end, messages.join("; "))
+                }
+            }
+            ErrorType::ReplacementTooLong => {
+                write!(f, "Replacement from hook is longer than the code it replaces.")
             }
         }
     }
 }
 
+impl std::error::Error for ErrorType {}
+
 /// Details about a minification failure, including where it occurred and why.
 #[derive(Debug)]
 pub struct Error {
@@ -34,6 +93,17 @@ pub struct Error {
     pub position: usize,
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at position {})", self.error_type, self.position)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error_type)
+    }
+}
 
 /// User-friendly details about a minification failure, including an English message description of
 /// the reason, and generated printable contextual representation of the code where the error
@@ -45,6 +115,14 @@ pub struct FriendlyError {
     pub code_context: String,
 }
 
+impl fmt::Display for FriendlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at position {})\n{}", self.message, self.position, self.code_context)
+    }
+}
+
+impl std::error::Error for FriendlyError {}
+
 pub type ProcessingResult<T> = Result<T, ErrorType>;
 
 #[inline(always)]
@@ -0,0 +1,52 @@
+/// What to do with an element or attribute, as returned by a `Hook`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HookAction {
+    /// Keep and minify it as usual.
+    Keep,
+    /// Remove it from the output.
+    Drop,
+    /// Replace it with other code. See the `Hook` methods for what is replaced.
+    Replace(Vec<u8>),
+}
+
+/// Callbacks to transform elements and attributes while minifying, such as to rewrite URLs or
+/// remove test attributes. Set it using `Cfg::hook`.
+///
+/// When minifying a slice in place, such as with `in_place`, a replacement can't be longer than the
+/// source code it replaces, or minification fails with `ErrorType::ReplacementTooLong`. `truncate`
+/// and `copy` grow the code as needed, so replacements can be any length.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use minify_html::{Cfg, copy, Hook, HookAction};
+///
+/// struct RemoveTestIds;
+///
+/// impl Hook for RemoveTestIds {
+///     fn attribute(&self, _element: &[u8], name: &[u8], _value: Option<&[u8]>) -> HookAction {
+///         if name.starts_with(b"data-test-") { HookAction::Drop } else { HookAction::Keep }
+///     }
+/// }
+///
+/// let cfg = Cfg { hook: Some(Arc::new(RemoveTestIds)), ..Cfg::default() };
+/// let minified = copy(b"<button data-test-id=submit class=primary>Go</button>", &cfg).unwrap();
+/// assert_eq!(minified, b"<button class=primary>Go</button>".to_vec());
+/// ```
+pub trait Hook: Send + Sync {
+    /// Called for each element with its lowercase tag name, before its attributes and content are
+    /// processed. `Drop` removes the entire element, including its content, and `Replace` replaces
+    /// the entire element with the provided HTML, written as is.
+    fn element(&self, _name: &[u8]) -> HookAction {
+        HookAction::Keep
+    }
+
+    /// Called for each attribute of an element that's kept, with the element's lowercase tag name
+    /// and the attribute's lowercase name and value (with entities decoded), if it has one. `Drop`
+    /// removes the attribute, and `Replace` replaces its value, which is encoded as necessary and
+    /// then minified as usual.
+    fn attribute(&self, _element: &[u8], _name: &[u8], _value: Option<&[u8]>) -> HookAction {
+        HookAction::Keep
+    }
+}
@@ -1,23 +1,47 @@
 pub use crate::err::{Error, ErrorType, FriendlyError};
 use crate::proc::Processor;
-use crate::unit::content::process_content;
-use crate::spec::tag::ns::Namespace;
-pub use crate::cfg::Cfg;
+use crate::unit::content::{Frame, resume};
+pub use crate::spec::tag::ns::Namespace;
+pub use crate::spec::tag::whitespace::WhitespaceGroup;
+pub use crate::cfg::{Cfg, MinifyFailurePolicy};
+pub use crate::cfg::selector::Selector;
 use crate::err::debug_repr;
+pub use crate::source_map::SourceMap;
+pub use crate::diagnostic::{Diagnostic, Severity};
+pub use crate::hook::{Hook, HookAction};
+pub use crate::tokenizer::{Attribute, EndTag, Span, StartTag, Token, Tokenizer};
+pub use crate::minifier::{BuiltinJsonMinifier, CodeKind, ContentMinifier, MinifyCache, MinifyError, MinifyResult};
+#[cfg(feature = "css-builtin")]
+pub use crate::minifier::BuiltinCssMinifier;
+#[cfg(feature = "js-esbuild")]
+pub use crate::minifier::{EsbuildMinifier, EsbuildOptions, EsbuildTarget};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
 mod cfg;
+mod diagnostic;
 mod err;
 mod gen;
+mod hook;
+mod minifier;
 mod pattern;
 #[macro_use]
 mod proc;
+mod source_map;
 mod spec;
+mod stream;
 mod tests;
+mod tokenizer;
 mod unit;
 
 /// Minifies a slice in-place and returns the new minified length.
 /// Any original code after the end of the minified code is left intact.
 ///
+/// As the slice can't grow, replacements from `cfg.hook` can't be longer than the code they
+/// replace, or minification fails with `ErrorType::ReplacementTooLong`. Use `truncate` or `copy`
+/// to allow any replacements.
+///
 /// # Arguments
 ///
 /// * `code` - A mutable slice of bytes representing the source code to minify.
@@ -32,6 +56,7 @@ mod unit;
 /// let cfg = &Cfg {
 ///     minify_js: false,
 ///     minify_css: false,
+///     ..Cfg::default()
 /// };
 /// match in_place(&mut code, cfg) {
 ///     Ok(minified_len) => assert_eq!(&code, b"<p>Hello, world!d!  </p>"),
@@ -40,17 +65,27 @@ mod unit;
 /// ```
 pub fn in_place(code: &mut [u8], cfg: &Cfg) -> Result<usize, Error> {
     let mut proc = Processor::new(code);
-    process_content(&mut proc, cfg, Namespace::Html, None, false)
-        .and_then(|_| if !proc.at_end() {
-            Err(ErrorType::UnexpectedClosingTag)
-        } else {
-            Ok(())
+    process_document(&mut proc, cfg)?;
+    proc.finish()
+}
+
+fn process_document(proc: &mut Processor, cfg: &Cfg) -> Result<(), Error> {
+    resume_document(proc, cfg, vec![Frame::document()]).map(|_| ())
+}
+
+// Process the rest of the document from where processing was suspended, returning where to resume from if it was
+// suspended again.
+fn resume_document(proc: &mut Processor, cfg: &Cfg, frames: Vec<Frame>) -> Result<Option<Vec<Frame>>, Error> {
+    resume(proc, cfg, frames)
+        .and_then(|content| match content.suspended {
+            Some(frames) => Ok(Some(frames)),
+            None if !proc.at_end() => Err(ErrorType::UnexpectedClosingTag),
+            None => Ok(None),
         })
-        .map_err(|error_type| Error {
+        .map_err(|error_type| proc.source_error(Error {
             error_type,
             position: proc.read_len(),
-        })?;
-    proc.finish()
+        }))
 }
 
 /// Minifies a str in-place and returns the new minified length.
@@ -70,6 +105,7 @@ pub fn in_place(code: &mut [u8], cfg: &Cfg) -> Result<usize, Error> {
 /// let cfg = &Cfg {
 ///     minify_js: false,
 ///     minify_css: false,
+///     ..Cfg::default()
 /// };
 /// match in_place_str(&mut code, cfg) {
 ///     Ok(minified_len) => assert_eq!(&code, "<p>Hello, world!d!  </p>"),
@@ -86,6 +122,9 @@ pub fn in_place_str<'s>(code: &'s mut str, cfg: &Cfg) -> Result<&'s str, Error>
 
 /// Minifies a Vec in-place, truncating it to the minified length.
 ///
+/// The Vec is grown if needed to fit replacements from `cfg.hook` that are longer than the code
+/// they replace.
+///
 /// # Arguments
 ///
 /// * `code` - A slice of bytes representing the source code to minify.
@@ -100,6 +139,7 @@ pub fn in_place_str<'s>(code: &'s mut str, cfg: &Cfg) -> Result<&'s str, Error>
 /// let cfg = &Cfg {
 ///     minify_js: false,
 ///     minify_css: false,
+///     ..Cfg::default()
 /// };
 /// match truncate(&mut code, cfg) {
 ///     Ok(()) => assert_eq!(code, b"<p>Hello, world!".to_vec()),
@@ -107,13 +147,11 @@ pub fn in_place_str<'s>(code: &'s mut str, cfg: &Cfg) -> Result<&'s str, Error>
 /// };
 /// ```
 pub fn truncate(code: &mut Vec<u8>, cfg: &Cfg) -> Result<(), Error> {
-    match in_place(code, cfg) {
-        Ok(written_len) => {
-            code.truncate(written_len);
-            Ok(())
-        }
-        Err(e) => Err(e),
-    }
+    let mut proc = Processor::new_growable(code);
+    process_document(&mut proc, cfg)?;
+    let written_len = proc.finish()?;
+    code.truncate(written_len);
+    Ok(())
 }
 
 /// Copies a slice into a new Vec and minifies it, returning the Vec.
@@ -133,6 +171,7 @@ pub fn truncate(code: &mut Vec<u8>, cfg: &Cfg) -> Result<(), Error> {
 /// let cfg = &Cfg {
 ///     minify_js: false,
 ///     minify_css: false,
+///     ..Cfg::default()
 /// };
 /// match copy(&code, cfg) {
 ///     Ok(minified) => {
@@ -150,6 +189,60 @@ pub fn copy(code: &[u8], cfg: &Cfg) -> Result<Vec<u8>, Error> {
     }
 }
 
+/// Minifies many slices in-place in parallel, and returns the result of minifying each slice in
+/// the same order, like `in_place`.
+///
+/// JS, CSS, and JSON minification results are shared between all documents using `cfg.cache`, or a
+/// new cache if it isn't set, so code that appears in many documents is only minified once. The
+/// cache keeps the result for each distinct section of code in memory until it's dropped, so
+/// minifying many documents with lots of distinct code uses memory proportional to that code. To
+/// limit it, set `cfg.cache` to a cache created using `MinifyCache::with_max_entries`.
+///
+/// # Arguments
+///
+/// * `codes` - Mutable slices of bytes representing the source code of each document to minify.
+/// * `cfg` - Configuration object to adjust minification approach.
+/// * `threads` - How many threads to minify documents on at once, or 0 to use one for each CPU.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html::{Cfg, in_place_batch};
+///
+/// let mut codes = vec![b"<p>  Hello, world!  </p>".to_vec(), b"<p>  Goodbye!  </p>".to_vec()];
+/// let results = in_place_batch(&mut codes, &Cfg::default(), 0);
+/// assert_eq!(&codes[0][..*results[0].as_ref().unwrap()], b"<p>Hello, world!");
+/// assert_eq!(&codes[1][..*results[1].as_ref().unwrap()], b"<p>Goodbye!");
+/// ```
+pub fn in_place_batch<C: AsMut<[u8]> + Send>(codes: &mut [C], cfg: &Cfg, threads: usize) -> Vec<Result<usize, Error>> {
+    let cfg = Cfg {
+        cache: Some(cfg.cache.clone().unwrap_or_else(|| Arc::new(MinifyCache::new()))),
+        ..cfg.clone()
+    };
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }.min(codes.len());
+    let mut results = Vec::<Option<Result<usize, Error>>>::new();
+    results.resize_with(codes.len(), || None);
+    let results = Mutex::new(results);
+    // Each thread takes the next document to minify until there are none left.
+    let next = Mutex::new(codes.iter_mut().enumerate());
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| loop {
+                let (i, code) = match next.lock().unwrap().next() {
+                    Some(next) => next,
+                    None => break,
+                };
+                let result = in_place(code.as_mut(), &cfg);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        };
+    });
+    results.into_inner().unwrap().into_iter().map(|r| r.unwrap()).collect()
+}
+
 /// Minifies a slice in-place and returns the new minified length.
 /// Any original code after the end of the minified code is left intact.
 ///
@@ -172,6 +265,7 @@ pub fn copy(code: &[u8], cfg: &Cfg) -> Result<Vec<u8>, Error> {
 /// let cfg = &Cfg {
 ///     minify_js: false,
 ///     minify_css: false,
+///     ..Cfg::default()
 /// };
 /// match with_friendly_error(&mut code, cfg) {
 ///     Ok(minified_len) => {}
@@ -192,3 +286,202 @@ pub fn with_friendly_error(code: &mut [u8], cfg: &Cfg) -> Result<usize, Friendly
         code_context: debug_repr(code, err.position as isize, -1),
     })
 }
+
+/// Minifies a slice in-place and returns the new minified length, as well as any sections of JS,
+/// CSS, or JSON code that couldn't be minified if `cfg.on_minify_failure` is
+/// `MinifyFailurePolicy::Warn`.
+/// Any original code after the end of the minified code is left intact.
+///
+/// This function is identical to `in_place` except it also returns warnings. Unlike
+/// `in_place_lenient`, it doesn't recover from errors in the HTML code.
+///
+/// # Arguments
+///
+/// * `code` - A mutable slice of bytes representing the source code to minify.
+/// * `cfg` - Configuration object to adjust minification approach.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html::{Cfg, ErrorType, in_place_with_warnings, MinifyFailurePolicy};
+///
+/// let mut code = b"<script type=application/json>{a}</script>".to_vec();
+/// let cfg = &Cfg {
+///     minify_json: true,
+///     on_minify_failure: MinifyFailurePolicy::Warn,
+///     ..Cfg::default()
+/// };
+/// let (minified_len, warnings) = in_place_with_warnings(&mut code, cfg).unwrap();
+/// assert_eq!(&code[..minified_len], b"<script type=application/json>{a}</script>");
+/// assert_eq!(warnings[0].error_type.code(), "minify-failed");
+/// ```
+pub fn in_place_with_warnings(code: &mut [u8], cfg: &Cfg) -> Result<(usize, Vec<Error>), Error> {
+    let mut proc = Processor::new(code);
+    process_document(&mut proc, cfg)?;
+    let len = proc.finish()?;
+    Ok((len, proc.take_warnings()))
+}
+
+/// Minifies a slice in-place and returns the new minified length, recovering from errors like
+/// browsers do instead of failing.
+/// Any original code after the end of the minified code is left intact.
+///
+/// Closing tags that don't match any open element are ignored (except for `</p>` and `</br>`,
+/// which are kept), a closing tag for an ancestor implicitly closes all elements inside it, and
+/// elements and comments still open at the end are implicitly closed. Each error recovered from is
+/// returned as a warning alongside the minified length. An error is still returned if it can't be
+/// recovered from.
+///
+/// # Arguments
+///
+/// * `code` - A mutable slice of bytes representing the source code to minify.
+/// * `cfg` - Configuration object to adjust minification approach.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html::{Cfg, Error, ErrorType, in_place_lenient};
+///
+/// let mut code = b"<div><span>  Hello, world!  </div></b>".to_vec();
+/// let cfg = &Cfg {
+///     minify_js: false,
+///     minify_css: false,
+///     ..Cfg::default()
+/// };
+/// match in_place_lenient(&mut code, cfg) {
+///     Ok((minified_len, warnings)) => {
+///         assert_eq!(&code[..minified_len], b"<div><span> Hello, world! </div>");
+///         assert_eq!(warnings.len(), 2);
+///         assert_eq!(warnings[1].error_type, ErrorType::UnexpectedClosingTag);
+///     }
+///     Err(Error { error_type, position }) => {}
+/// };
+/// ```
+pub fn in_place_lenient(code: &mut [u8], cfg: &Cfg) -> Result<(usize, Vec<Error>), Error> {
+    let mut proc = Processor::new(code);
+    proc.enable_lenient();
+    process_document(&mut proc, cfg)?;
+    let len = proc.finish()?;
+    Ok((len, proc.take_warnings()))
+}
+
+/// Finds all problems in the source code that can be found in one pass, without modifying it.
+///
+/// The source code is processed the same way as `in_place_lenient`, and each error recovered from
+/// is returned as a warning. Invalid numeric entities, duplicate attributes, and closing tags for
+/// void elements are also reported as warnings. If an error can't be recovered from, it's returned
+/// last and nothing after it is checked. Diagnostics are sorted by position.
+///
+/// # Arguments
+///
+/// * `code` - A slice of bytes representing the source code to check.
+/// * `cfg` - Configuration object to adjust parsing approach.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html::{Cfg, ErrorType, Severity, diagnose};
+///
+/// let code = b"<div>\n  <img src=a src=b></img>\n  &#xD800;";
+/// let diagnostics = diagnose(code, &Cfg::default());
+/// assert_eq!(diagnostics.len(), 4);
+/// assert_eq!(diagnostics[0].error_type, ErrorType::UnclosedElement("div".to_string()));
+/// assert_eq!(diagnostics[1].error_type, ErrorType::DuplicateAttribute("src".to_string()));
+/// assert_eq!(diagnostics[1].severity, Severity::Warning);
+/// assert_eq!((diagnostics[1].line, diagnostics[1].column), (2, 14));
+/// ```
+pub fn diagnose(code: &[u8], cfg: &Cfg) -> Vec<Diagnostic> {
+    let mut copy = code.to_vec();
+    let mut proc = Processor::new(&mut copy);
+    proc.enable_lenient();
+    let error = process_document(&mut proc, cfg).and_then(|_| proc.finish()).err();
+    let mut warnings = proc.take_warnings();
+    warnings.sort_by_key(|w| w.position);
+    warnings
+        .into_iter()
+        .map(|w| Diagnostic::new(code, w, Severity::Warning))
+        .chain(error.map(|e| Diagnostic::new(code, e, Severity::Error)))
+        .collect()
+}
+
+/// Minifies a slice in-place and returns the new minified length, as well as a source map of the
+/// minified code back to the original source code.
+/// Any original code after the end of the minified code is left intact.
+///
+/// As the source code is overwritten, keep a copy of it if it will be needed later, such as for
+/// generating a [Source Map v3](https://sourcemaps.info/spec.html) document using
+/// `SourceMap::to_json`.
+///
+/// # Arguments
+///
+/// * `code` - A mutable slice of bytes representing the source code to minify.
+/// * `cfg` - Configuration object to adjust minification approach.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html::{Cfg, Error, with_source_map};
+///
+/// let source: &[u8] = b"<p>  Hello, world!  </p>";
+/// let mut code = source.to_vec();
+/// let cfg = &Cfg {
+///     minify_js: false,
+///     minify_css: false,
+///     ..Cfg::default()
+/// };
+/// match with_source_map(&mut code, cfg) {
+///     Ok((minified_len, source_map)) => {
+///         assert_eq!(&code[..minified_len], b"<p>Hello, world!");
+///         // The "H" in the minified code came from position 5 in the source code.
+///         assert_eq!(source_map.source_position(3), 5);
+///         let json = source_map.to_json(source, &code[..minified_len], "index.html");
+///         assert!(json.starts_with(r#"{"version":3,"sources":["index.html"]"#));
+///     }
+///     Err(Error { error_type, position }) => {}
+/// };
+/// ```
+pub fn with_source_map(code: &mut [u8], cfg: &Cfg) -> Result<(usize, SourceMap), Error> {
+    let mut proc = Processor::new(code);
+    proc.enable_source_map();
+    process_document(&mut proc, cfg)?;
+    proc.finish_with_source_map().map(|(len, map)| (len, map.unwrap()))
+}
+
+/// Reads source code from `input` in chunks, minifies it, and writes the minified code to `output`
+/// as it goes. The output is the same as minifying all of the source code at once.
+///
+/// Minified code is written as soon as it no longer depends on code that hasn't been read yet.
+/// Only unresolved code is held back, like whitespace or a closing tag that could be omitted
+/// depending on what follows, a partial entity, or an element that hasn't been read completely,
+/// along with the names of open elements. Elements and other constructs can't be split, so memory
+/// usage is bounded by the largest of them (e.g. a big `<script>`) rather than the whole input.
+///
+/// Minification errors are returned as an `io::Error` of kind `InvalidData` that wraps the `Error`.
+/// Positions in the error are in the entire source code, and minified code from before the error
+/// may have already been written to `output`. Hooks could be called more than once for the same
+/// element.
+///
+/// # Arguments
+///
+/// * `input` - Where to read the source code from.
+/// * `output` - Where to write the minified code to.
+/// * `cfg` - Configuration object to adjust minification approach.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html::{Cfg, stream};
+///
+/// let mut input: &[u8] = b"<p>  Hello, world!  </p>";
+/// let mut output = Vec::<u8>::new();
+/// let cfg = &Cfg {
+///     minify_js: false,
+///     minify_css: false,
+///     ..Cfg::default()
+/// };
+/// stream(&mut input, &mut output, cfg).unwrap();
+/// assert_eq!(output, b"<p>Hello, world!".to_vec());
+/// ```
+pub fn stream<R: Read, W: Write>(input: &mut R, output: &mut W, cfg: &Cfg) -> io::Result<()> {
+    stream::minify_chunks(input, output, cfg)
+}
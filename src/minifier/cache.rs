@@ -0,0 +1,234 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::minifier::{CodeKind, ContentMinifier, MinifyResult};
+
+type Done = Box<dyn FnOnce(MinifyResult) + Send>;
+
+enum Entry {
+    // Being minified, with callbacks for the same code requested in the meantime.
+    Pending(Vec<Done>),
+    Minified(MinifyResult),
+}
+
+// Identifies code and everything that affects how it's minified, without keeping the code itself.
+#[derive(Clone, Eq, Hash, PartialEq)]
+struct Key {
+    kind: CodeKind,
+    options: String,
+    len: usize,
+    // A 128-bit FNV-1a hash of the code.
+    hash: u128,
+    // A hash of the code keyed using the cache's `RandomState`. Unlike FNV-1a hashes, code can't be chosen to have the
+    // same keyed hash as other code, so different code is only mixed up if it happens to have the same length and both
+    // hashes.
+    keyed_hash: u64,
+}
+
+/// A cache of minified JS, CSS, and JSON code, so that identical code (e.g. the same inline script
+/// on many pages) is only minified once. Set it using `Cfg::cache`; it can be shared between
+/// documents and threads, and `in_place_batch` uses one for all documents.
+///
+/// Code is looked up using its kind, the minifier's `ContentMinifier::cache_key`, and the code's
+/// length and hashes, so the code itself isn't kept in memory. If the cache has a directory,
+/// successfully minified code is also stored there, so it can be reused between runs. Files are
+/// named using a 128-bit FNV-1a hash, and also contain the code they're for, which is checked when
+/// reading them so that code with the same hash is never mixed up.
+///
+/// By default, every result is kept in memory for as long as the cache is used. To limit the
+/// memory used, set a maximum number of results using `MinifyCache::with_max_entries`, after
+/// which the oldest results are removed.
+#[derive(Default)]
+pub struct MinifyCache {
+    entries: Mutex<Entries>,
+    dir: Option<PathBuf>,
+    max_entries: Option<usize>,
+    hash_state: RandomState,
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<Key, Entry>,
+    // Keys of minified entries, from oldest to newest.
+    minified: VecDeque<Key>,
+}
+
+// Removes a pending entry if its minification callback is dropped without being called, so that callbacks waiting on
+// it are dropped too, as the minifier would have done.
+struct PendingGuard {
+    cache: Arc<MinifyCache>,
+    key: Option<Key>,
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let mut entries = self.cache.entries.lock().unwrap();
+            if let Some(Entry::Pending(_)) = entries.map.get(&key) {
+                entries.map.remove(&key);
+            };
+        };
+    }
+}
+
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+#[inline(always)]
+fn fnv1a(mut hash: u128, bytes: &[u8]) -> u128 {
+    for &b in bytes {
+        hash ^= b as u128;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    };
+    hash
+}
+
+// Combine the code with everything that affects how it's minified, which is what a file in the cache's directory is
+// for.
+fn file_key(key: &Key, code: &[u8]) -> Vec<u8> {
+    let mut file_key = Vec::with_capacity(9 + key.options.len() + code.len());
+    file_key.push(key.kind as u8);
+    // Include the length so that the options and code can't run into each other.
+    file_key.extend_from_slice(&(key.options.len() as u64).to_le_bytes());
+    file_key.extend_from_slice(key.options.as_bytes());
+    file_key.extend_from_slice(code);
+    file_key
+}
+
+impl MinifyCache {
+    /// Create an in-memory cache.
+    pub fn new() -> MinifyCache {
+        MinifyCache::default()
+    }
+
+    /// Create a cache that also stores minified code as files in `dir`, which is created if it
+    /// doesn't exist. Code in the directory from a previous run is used instead of minifying the
+    /// same code again. Failing to read or write a file is ignored.
+    pub fn with_dir<P: Into<PathBuf>>(dir: P) -> io::Result<MinifyCache> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(MinifyCache {
+            dir: Some(dir),
+            ..MinifyCache::default()
+        })
+    }
+
+    /// Keep at most `max_entries` minified results in memory, removing the oldest when another is
+    /// added. Results stored in the cache's directory are kept.
+    pub fn with_max_entries(self, max_entries: usize) -> MinifyCache {
+        MinifyCache {
+            max_entries: Some(max_entries),
+            ..self
+        }
+    }
+
+    /// How many distinct sections of code have been minified or are being minified.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().map.len()
+    }
+
+    /// Whether no code has been minified or is being minified.
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().unwrap().map.is_empty()
+    }
+
+    fn key(&self, minifier: &dyn ContentMinifier, kind: CodeKind, code: &[u8]) -> Key {
+        let mut keyed_hasher = self.hash_state.build_hasher();
+        keyed_hasher.write(code);
+        Key {
+            kind,
+            options: minifier.cache_key(),
+            len: code.len(),
+            hash: fnv1a(FNV_OFFSET_BASIS, code),
+            keyed_hash: keyed_hasher.finish(),
+        }
+    }
+
+    fn path(&self, file_key: &[u8]) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{:032x}", fnv1a(FNV_OFFSET_BASIS, file_key))))
+    }
+
+    // Read the minified code for `file_key` from its file, which starts with the length of the key and the key itself.
+    fn read(&self, file_key: &[u8]) -> Option<Vec<u8>> {
+        let mut contents = fs::read(self.path(file_key)?).ok()?;
+        let key_len = u64::from_le_bytes(contents.get(..8)?.try_into().unwrap()) as usize;
+        if contents.get(8..)?.get(..key_len)? != file_key {
+            return None;
+        };
+        Some(contents.split_off(8 + key_len))
+    }
+
+    fn write(&self, file_key: &[u8], min_code: &[u8]) -> () {
+        let path = match self.path(file_key) {
+            Some(path) => path,
+            None => return,
+        };
+        let mut contents = Vec::with_capacity(8 + file_key.len() + min_code.len());
+        contents.extend_from_slice(&(file_key.len() as u64).to_le_bytes());
+        contents.extend_from_slice(file_key);
+        contents.extend_from_slice(min_code);
+        // Write to a temporary file first so that other processes never read a partially written file.
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        if fs::write(&tmp, contents).is_ok() {
+            let _ = fs::rename(&tmp, &path);
+        };
+    }
+
+    // Store the result for `key` and call any callbacks waiting for it.
+    fn complete(&self, key: Key, result: &MinifyResult) -> () {
+        let waiting = {
+            let mut entries = self.entries.lock().unwrap();
+            let waiting = match entries.map.insert(key.clone(), Entry::Minified(result.clone())) {
+                Some(Entry::Pending(waiting)) => waiting,
+                _ => Vec::new(),
+            };
+            entries.minified.push_back(key);
+            if entries.minified.len() > self.max_entries.unwrap_or(usize::MAX) {
+                let oldest = entries.minified.pop_front().unwrap();
+                entries.map.remove(&oldest);
+            };
+            waiting
+        };
+        for w in waiting {
+            w(result.clone());
+        };
+    }
+
+    // Minify using `minifier`, unless the same code has already been minified or is being minified.
+    pub(crate) fn minify_async(cache: &Arc<MinifyCache>, minifier: &dyn ContentMinifier, kind: CodeKind, code: Vec<u8>, done: Done) -> () {
+        let key = cache.key(minifier, kind, &code);
+        {
+            let mut entries = cache.entries.lock().unwrap();
+            match entries.map.get_mut(&key) {
+                Some(Entry::Minified(result)) => {
+                    let result = result.clone();
+                    drop(entries);
+                    return done(result);
+                }
+                Some(Entry::Pending(waiting)) => return waiting.push(done),
+                None => entries.map.insert(key.clone(), Entry::Pending(Vec::new())),
+            };
+        };
+        let file_key = cache.dir.as_ref().map(|_| file_key(&key, &code));
+        if let Some(min_code) = file_key.as_ref().and_then(|file_key| cache.read(file_key)) {
+            let result = Ok(min_code);
+            cache.complete(key, &result);
+            return done(result);
+        };
+        let mut guard = PendingGuard { cache: cache.clone(), key: Some(key) };
+        minifier.minify_async(kind, code, Box::new(move |result| {
+            let key = guard.key.take().unwrap();
+            if let (Some(file_key), Ok(min_code)) = (&file_key, &result) {
+                guard.cache.write(file_key, min_code);
+            };
+            guard.cache.complete(key, &result);
+            done(result);
+        }));
+    }
+}
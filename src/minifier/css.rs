@@ -0,0 +1,317 @@
+use crate::minifier::{CodeKind, ContentMinifier, MinifyError, MinifyResult};
+
+// Length units that can be omitted from zero values. Other units, like `s` and `%`, aren't always optional.
+static ZERO_OPTIONAL_UNITS: &[&[u8]] = &[
+    b"px", b"em", b"rem", b"ex", b"ch", b"vw", b"vh", b"vmin", b"vmax", b"cm", b"mm", b"q", b"in", b"pt", b"pc",
+];
+
+/// A simple CSS minifier that only removes comments and unnecessary whitespace and semicolons, and shortens colours
+/// and numbers. Available with the `css-builtin` feature, and used by default if the `js-esbuild` feature isn't
+/// enabled. JS and JSON are not supported.
+pub struct BuiltinCssMinifier;
+
+impl ContentMinifier for BuiltinCssMinifier {
+    fn minify(&self, kind: CodeKind, code: &[u8]) -> MinifyResult {
+        let min_code = match kind {
+            CodeKind::Css => minify_css(code, false),
+            CodeKind::CssDeclarations => minify_css(code, true),
+            _ => return Err(MinifyError::Unsupported),
+        };
+        min_code.ok_or_else(|| MinifyError::Failed(vec!["Unterminated string or comment, or unbalanced braces.".to_string()]))
+    }
+
+    fn cache_key(&self) -> String {
+        format!("css-builtin {}", env!("CARGO_PKG_VERSION"))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Context {
+    // A selector, at-rule prelude, or at-rule statement.
+    Prelude,
+    // A declaration's property name.
+    Name,
+    // A declaration's value.
+    Value,
+}
+
+#[inline(always)]
+fn is_whitespace(c: u8) -> bool {
+    c == b' ' || c == b'\t' || c == b'\n' || c == b'\r' || c == b'\x0c'
+}
+
+#[inline(always)]
+fn is_ident_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'-' || c == b'_' || c >= 0x80
+}
+
+// Whether a number can start after `out`, and isn't part of something else like an identifier (e.g. `h1` or `col-01`).
+fn can_start_number(out: &[u8]) -> bool {
+    match out.last() {
+        None => true,
+        Some(b'-') => out.len() < 2 || !is_ident_char(out[out.len() - 2]),
+        Some(c) => !is_ident_char(*c) && *c != b'.' && *c != b'#',
+    }
+}
+
+// Get the position after the string starting at `start`, or None if it's not terminated.
+fn string_end(code: &[u8], start: usize) -> Option<usize> {
+    let quote = code[start];
+    let mut pos = start + 1;
+    loop {
+        match code.get(pos)? {
+            b'\\' => pos += 2,
+            c if *c == quote => return Some(pos + 1),
+            _ => pos += 1,
+        };
+    };
+}
+
+// Get the position after the comment starting at `start`, or None if it's not terminated.
+fn comment_end(code: &[u8], start: usize) -> Option<usize> {
+    code[start + 2..].windows(2).position(|w| w == b"*/").map(|p| start + 2 + p + 2)
+}
+
+// Determine what a statement starting at `start` is by finding what ends it. If it's a declaration, it would end at
+// `;` or `}`; otherwise, it's a selector or at-rule with a block, or an at-rule statement like `@import`.
+fn statement_context(code: &[u8], start: usize, in_block: bool) -> Option<Context> {
+    let mut pos = start;
+    let mut first = None;
+    while let Some(&c) = code.get(pos) {
+        if first.is_none() && !is_whitespace(c) {
+            first = Some(c);
+        };
+        match c {
+            b'"' | b'\'' => pos = string_end(code, pos)?,
+            b'/' if code.get(pos + 1) == Some(&b'*') => pos = comment_end(code, pos)?,
+            b'\\' => pos += 2,
+            b'{' => return Some(Context::Prelude),
+            b';' | b'}' => break,
+            _ => pos += 1,
+        };
+    };
+    Some(if in_block && first != Some(b'@') { Context::Name } else { Context::Prelude })
+}
+
+// Write the number starting at `start` as short as possible, dropping the unit if it's zero, the unit is optional, and
+// not `keep_unit`. Returns the position after the number and its unit.
+fn write_number(code: &[u8], start: usize, keep_unit: bool, out: &mut Vec<u8>) -> usize {
+    let mut pos = start;
+    while code.get(pos).filter(|c| c.is_ascii_digit()).is_some() {
+        pos += 1;
+    };
+    let int_end = pos;
+    if code.get(pos) == Some(&b'.') && code.get(pos + 1).filter(|c| c.is_ascii_digit()).is_some() {
+        pos += 1;
+        while code.get(pos).filter(|c| c.is_ascii_digit()).is_some() {
+            pos += 1;
+        };
+    };
+    let num_end = pos;
+    if code.get(pos).filter(|c| **c == b'e' || **c == b'E').is_some()
+        && code.get(pos + 1).filter(|c| c.is_ascii_digit() || **c == b'+' || **c == b'-').is_some() {
+        // Don't bother with scientific notation.
+        out.extend_from_slice(&code[start..pos]);
+        return pos;
+    };
+    while code.get(pos).filter(|c| c.is_ascii_alphabetic() || **c == b'%').is_some() {
+        pos += 1;
+    };
+    let unit = &code[num_end..pos];
+
+    let int = &code[start..int_end];
+    let int = &int[int.iter().position(|c| *c != b'0').unwrap_or(int.len())..];
+    let frac = if num_end > int_end { &code[int_end + 1..num_end] } else { &code[num_end..num_end] };
+    let frac = &frac[..frac.iter().rposition(|c| *c != b'0').map_or(0, |p| p + 1)];
+    if int.is_empty() && frac.is_empty() {
+        out.push(b'0');
+        if keep_unit || !ZERO_OPTIONAL_UNITS.iter().any(|u| unit.eq_ignore_ascii_case(u)) {
+            out.extend_from_slice(unit);
+        };
+    } else {
+        out.extend_from_slice(int);
+        if !frac.is_empty() {
+            out.push(b'.');
+            out.extend_from_slice(frac);
+        };
+        out.extend_from_slice(unit);
+    };
+    pos
+}
+
+// Write the hex colour starting at `start` (at `#`) in its short form if possible. Returns the position after it.
+fn write_colour(code: &[u8], start: usize, out: &mut Vec<u8>) -> usize {
+    let mut end = start + 1;
+    while code.get(end).filter(|c| is_ident_char(**c)).is_some() {
+        end += 1;
+    };
+    let hex = &code[start + 1..end];
+    out.push(b'#');
+    if (hex.len() == 6 || hex.len() == 8)
+        && hex.iter().all(|c| c.is_ascii_hexdigit())
+        && hex.chunks(2).all(|p| p[0].eq_ignore_ascii_case(&p[1])) {
+        out.extend(hex.iter().step_by(2).map(|c| c.to_ascii_lowercase()));
+    } else {
+        out.extend_from_slice(hex);
+    };
+    end
+}
+
+// Copy a custom property value starting at `start` as is, except for surrounding whitespace, as whitespace and units
+// in it can matter. Returns the position of the `;` or `}` that ends it.
+fn write_custom_property_value(code: &[u8], start: usize, out: &mut Vec<u8>) -> Option<usize> {
+    let mut pos = start;
+    let mut depth = 0usize;
+    while let Some(&c) = code.get(pos) {
+        match c {
+            b'"' | b'\'' => pos = string_end(code, pos)?,
+            b'\\' => pos += 2,
+            b'(' | b'[' | b'{' => {
+                depth += 1;
+                pos += 1;
+            }
+            b';' | b'}' if depth == 0 => break,
+            b')' | b']' | b'}' => {
+                depth = depth.saturating_sub(1);
+                pos += 1;
+            }
+            _ => pos += 1,
+        };
+    };
+    let pos = pos.min(code.len());
+    let value = &code[start..pos];
+    let value_start = value.iter().position(|c| !is_whitespace(*c)).unwrap_or(value.len());
+    let value_end = value.iter().rposition(|c| !is_whitespace(*c)).map_or(0, |p| p + 1);
+    if value_start < value_end {
+        out.extend_from_slice(&value[value_start..value_end]);
+    };
+    Some(pos)
+}
+
+// Minify a stylesheet, or a list of declarations if `declarations`. Returns None if the code has an unterminated string
+// or comment, or unbalanced braces.
+fn minify_css(code: &[u8], declarations: bool) -> Option<Vec<u8>> {
+    let mut out = Vec::<u8>::with_capacity(code.len());
+    let mut pos = 0;
+    // How many blocks we're in, where the top level of a list of declarations counts as a block.
+    let mut depth = declarations as usize;
+    let mut parens = 0usize;
+    let mut ctx = statement_context(code, 0, depth > 0)?;
+    let mut name_start = 0;
+    let mut pending_space = false;
+    while let Some(&c) = code.get(pos) {
+        if is_whitespace(c) {
+            pending_space = true;
+            pos += 1;
+            continue;
+        };
+        if c == b'/' && code.get(pos + 1) == Some(&b'*') {
+            pos = comment_end(code, pos)?;
+            pending_space = true;
+            continue;
+        };
+        if pending_space {
+            pending_space = false;
+            let needed = match (out.last(), c) {
+                (None, _) => false,
+                (Some(b'{' | b'}' | b';' | b',' | b'>' | b'~' | b'('), _) => false,
+                (_, b'{' | b'}' | b';' | b',' | b'>' | b'~' | b')' | b'!') => false,
+                (Some(b':'), _) => ctx == Context::Prelude && parens == 0,
+                (_, b':') => ctx == Context::Value || ctx == Context::Prelude && parens == 0,
+                _ => true,
+            };
+            if needed {
+                out.push(b' ');
+            };
+        };
+        match c {
+            b'"' | b'\'' => {
+                let end = string_end(code, pos)?;
+                out.extend_from_slice(&code[pos..end]);
+                pos = end;
+            }
+            b'\\' => {
+                out.extend_from_slice(&code[pos..(pos + 2).min(code.len())]);
+                pos += 2;
+            }
+            b'{' => {
+                out.push(c);
+                pos += 1;
+                depth += 1;
+                parens = 0;
+                ctx = statement_context(code, pos, true)?;
+                name_start = out.len();
+            }
+            b'}' => {
+                if out.last() == Some(&b';') {
+                    out.pop();
+                };
+                out.push(c);
+                pos += 1;
+                depth = depth.checked_sub(1).filter(|d| *d > 0 || !declarations)?;
+                parens = 0;
+                ctx = statement_context(code, pos, depth > 0)?;
+                name_start = out.len();
+            }
+            b';' if parens == 0 => {
+                if out.last().filter(|c| **c != b';' && **c != b'{').is_some() {
+                    out.push(c);
+                };
+                pos += 1;
+                ctx = statement_context(code, pos, depth > 0)?;
+                name_start = out.len();
+            }
+            b':' if ctx == Context::Name => {
+                let is_custom_property = out[name_start..].starts_with(b"--");
+                out.push(c);
+                pos += 1;
+                if is_custom_property {
+                    pos = write_custom_property_value(code, pos, &mut out)?;
+                } else {
+                    ctx = Context::Value;
+                };
+            }
+            b'(' => {
+                out.push(c);
+                pos += 1;
+                parens += 1;
+                let is_url = out.len() >= 4
+                    && out[out.len() - 4..out.len() - 1].eq_ignore_ascii_case(b"url")
+                    && out.get(out.len().wrapping_sub(5)).filter(|c| is_ident_char(**c)).is_none();
+                if is_url {
+                    let arg_start = pos + code[pos..].iter().position(|c| !is_whitespace(*c)).unwrap_or(code.len() - pos);
+                    if code.get(arg_start).filter(|c| **c != b'"' && **c != b'\'').is_some() {
+                        // Unquoted URLs are copied as is.
+                        let arg_end = arg_start + code[arg_start..].iter().position(|c| *c == b')')?;
+                        let arg = &code[arg_start..arg_end];
+                        out.extend_from_slice(&arg[..arg.iter().rposition(|c| !is_whitespace(*c)).map_or(0, |p| p + 1)]);
+                        pos = arg_end;
+                    };
+                };
+            }
+            b')' => {
+                out.push(c);
+                pos += 1;
+                parens = parens.saturating_sub(1);
+            }
+            b'#' if ctx == Context::Value => pos = write_colour(code, pos, &mut out),
+            c if ctx == Context::Value
+                && (c.is_ascii_digit() || c == b'.' && code.get(pos + 1).filter(|c| c.is_ascii_digit()).is_some())
+                && can_start_number(&out) => {
+                // Math functions like `calc` need units even for zero lengths, so keep them in all functions.
+                pos = write_number(code, pos, parens > 0, &mut out);
+            }
+            _ => {
+                out.push(c);
+                pos += 1;
+            }
+        };
+    };
+    if depth != declarations as usize {
+        return None;
+    };
+    if declarations && out.last() == Some(&b';') {
+        out.pop();
+    };
+    Some(out)
+}
@@ -0,0 +1,147 @@
+use std::sync::Arc;
+use std::sync::mpsc::channel;
+
+use esbuild_rs::{Format, Loader, Message, Target, TransformOptions, TransformOptionsBuilder};
+
+use crate::minifier::{BuiltinJsonMinifier, CodeKind, ContentMinifier, MinifyError, MinifyResult};
+
+/// Options for `EsbuildMinifier`. To use them, set `Cfg::minifier` to an `EsbuildMinifier` created
+/// using `EsbuildMinifier::with_options`.
+///
+/// The version of esbuild used doesn't support the `charset` and `legalComments` options, so
+/// non-ASCII characters are always escaped and all comments are removed.
+#[derive(Clone, Copy)]
+pub struct EsbuildOptions {
+    /// The JavaScript version that minified code must be compatible with, such as
+    /// `EsbuildTarget::ES2015` for older browsers. Minification won't use newer syntax, and code
+    /// using syntax that can't be lowered to this version fails to minify. Defaults to
+    /// `EsbuildTarget::ESNext`.
+    pub target: Target,
+    /// If enabled, local variables, functions, and classes are not renamed, so code that depends
+    /// on names (e.g. using `Function.prototype.name`) keeps working. Top-level names in scripts
+    /// are global, so they're never renamed.
+    pub keep_names: bool,
+}
+
+impl Default for EsbuildOptions {
+    fn default() -> EsbuildOptions {
+        EsbuildOptions {
+            target: Target::ESNext,
+            keep_names: false,
+        }
+    }
+}
+
+/// Minifies JS and CSS using [esbuild-rs](https://github.com/wilsonzlin/esbuild-rs). This is the
+/// default minifier when the `js-esbuild` feature is enabled. JSON is minified using
+/// `BuiltinJsonMinifier`.
+pub struct EsbuildMinifier {
+    options: EsbuildOptions,
+    js: Arc<TransformOptions>,
+    js_module: Arc<TransformOptions>,
+    css: Arc<TransformOptions>,
+}
+
+impl EsbuildMinifier {
+    /// Create a minifier using the default options.
+    pub fn new() -> EsbuildMinifier {
+        EsbuildMinifier::with_options(EsbuildOptions::default())
+    }
+
+    /// Create a minifier using `options`.
+    pub fn with_options(options: EsbuildOptions) -> EsbuildMinifier {
+        let js_builder = || {
+            let mut js = TransformOptionsBuilder::new();
+            js.target = options.target;
+            js.minify_identifiers = !options.keep_names;
+            js.minify_syntax = true;
+            js.minify_whitespace = true;
+            js
+        };
+        let js = js_builder();
+        // Modules have their own scope and can contain `import` and `export` statements.
+        let mut js_module = js_builder();
+        js_module.format = Format::ESModule;
+        let mut css = TransformOptionsBuilder::new();
+        css.loader = Loader::CSS;
+        css.target = options.target;
+        css.minify_identifiers = true;
+        css.minify_syntax = true;
+        css.minify_whitespace = true;
+        EsbuildMinifier {
+            options,
+            js: js.build(),
+            js_module: js_module.build(),
+            css: css.build(),
+        }
+    }
+}
+
+// Describe an esbuild message, with its location relative to the code before it was wrapped with `prefix`.
+fn message(message: &Message, prefix: &str) -> String {
+    match &message.location {
+        Some(loc) => {
+            // The prefix is only on the first line.
+            let column = if loc.line == 1 { loc.column - prefix.len() as isize } else { loc.column };
+            format!("{}:{}: {}", loc.line, column + 1, message.text.as_str())
+        }
+        None => message.text.as_str().to_string(),
+    }
+}
+
+impl ContentMinifier for EsbuildMinifier {
+    fn minify(&self, kind: CodeKind, code: &[u8]) -> MinifyResult {
+        let (send, recv) = channel();
+        self.minify_async(kind, code.to_vec(), Box::new(move |result| {
+            // The receiver can't be dropped before this is called.
+            send.send(result).unwrap();
+        }));
+        recv.recv().unwrap_or_else(|_| Err(MinifyError::Failed(Vec::new())))
+    }
+
+    fn minify_async(&self, kind: CodeKind, code: Vec<u8>, done: Box<dyn FnOnce(MinifyResult) + Send>) -> () {
+        // esbuild can only minify entire scripts and stylesheets, so wrap partial code in something that esbuild will
+        // leave as is.
+        let (options, prefix, suffix) = match kind {
+            CodeKind::Js => (&self.js, "", ""),
+            CodeKind::JsModule => (&self.js_module, "", ""),
+            CodeKind::JsFunctionBody => (&self.js, "function x(){", "}"),
+            CodeKind::Css => (&self.css, "", ""),
+            CodeKind::CssDeclarations => (&self.css, "x{", "}"),
+            CodeKind::Json => return done(BuiltinJsonMinifier.minify(kind, &code)),
+        };
+        let code = if prefix.is_empty() {
+            code
+        } else {
+            let mut wrapped = prefix.as_bytes().to_vec();
+            wrapped.extend(code);
+            wrapped.extend_from_slice(suffix.as_bytes());
+            wrapped
+        };
+        esbuild_rs::transform_direct(Arc::new(code), options.clone(), move |result| {
+            let min_code = result.code.as_str().trim();
+            done(if !result.errors.is_empty() {
+                Err(MinifyError::Failed(result.errors.as_slice().iter().map(|m| message(m, prefix)).collect()))
+            } else if prefix.is_empty() || min_code.is_empty() {
+                // esbuild removes code that does nothing, like empty rules.
+                Ok(min_code.as_bytes().to_vec())
+            } else {
+                min_code
+                    .strip_prefix(prefix)
+                    .and_then(|c| c.strip_suffix(suffix))
+                    .map(|c| c.as_bytes().to_vec())
+                    .ok_or_else(|| MinifyError::Failed(vec!["Could not unwrap minified code.".to_string()]))
+            });
+        });
+    }
+
+    fn cache_key(&self) -> String {
+        // The version of esbuild used depends on the version of this crate.
+        format!(
+            "esbuild {} target={} keep_names={}",
+            env!("CARGO_PKG_VERSION"),
+            self.options.target as u8,
+            self.options.keep_names,
+        )
+    }
+}
@@ -0,0 +1,148 @@
+use crate::minifier::{CodeKind, ContentMinifier, MinifyError, MinifyResult};
+
+static LITERALS: &[&[u8]] = &[b"true", b"false", b"null"];
+
+/// Validates JSON and removes whitespace outside of strings. Strings and numbers are kept as is.
+/// This is always available, and used for JSON if `Cfg::minifier` is not set. Only JSON is
+/// supported.
+pub struct BuiltinJsonMinifier;
+
+impl ContentMinifier for BuiltinJsonMinifier {
+    fn minify(&self, kind: CodeKind, code: &[u8]) -> MinifyResult {
+        match kind {
+            CodeKind::Json => minify_json(code).ok_or_else(|| MinifyError::Failed(vec!["Invalid JSON.".to_string()])),
+            _ => Err(MinifyError::Unsupported),
+        }
+    }
+
+    fn cache_key(&self) -> String {
+        format!("json-builtin {}", env!("CARGO_PKG_VERSION"))
+    }
+}
+
+#[inline(always)]
+fn skip_whitespace(code: &[u8], mut pos: usize) -> usize {
+    while code.get(pos).filter(|c| matches!(c, b' ' | b'\t' | b'\n' | b'\r')).is_some() {
+        pos += 1;
+    };
+    pos
+}
+
+// Get the position after the digits starting at `pos`, or None if there are none.
+fn digits_end(code: &[u8], pos: usize) -> Option<usize> {
+    let len = code[pos.min(code.len())..].iter().take_while(|c| c.is_ascii_digit()).count();
+    if len == 0 { None } else { Some(pos + len) }
+}
+
+// Write the string starting at `start` (at `"`) as is. Returns the position after it, or None if it's invalid.
+fn write_string(code: &[u8], start: usize, out: &mut Vec<u8>) -> Option<usize> {
+    if code.get(start) != Some(&b'"') {
+        return None;
+    };
+    let mut pos = start + 1;
+    loop {
+        match *code.get(pos)? {
+            b'"' => break,
+            b'\\' => pos += match *code.get(pos + 1)? {
+                b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => 2,
+                b'u' if code.get(pos + 2..pos + 6).filter(|h| h.iter().all(|c| c.is_ascii_hexdigit())).is_some() => 6,
+                _ => return None,
+            },
+            // Control characters must be escaped.
+            c if c < 0x20 => return None,
+            _ => pos += 1,
+        };
+    };
+    out.extend_from_slice(&code[start..=pos]);
+    Some(pos + 1)
+}
+
+// Write the number starting at `start` as is. Returns the position after it, or None if it's invalid.
+fn write_number(code: &[u8], start: usize, out: &mut Vec<u8>) -> Option<usize> {
+    let mut pos = start;
+    if code.get(pos) == Some(&b'-') {
+        pos += 1;
+    };
+    // Leading zeros aren't allowed.
+    pos = if code.get(pos) == Some(&b'0') { pos + 1 } else { digits_end(code, pos)? };
+    if code.get(pos) == Some(&b'.') {
+        pos = digits_end(code, pos + 1)?;
+    };
+    if code.get(pos).filter(|c| **c == b'e' || **c == b'E').is_some() {
+        pos += 1;
+        if code.get(pos).filter(|c| **c == b'+' || **c == b'-').is_some() {
+            pos += 1;
+        };
+        pos = digits_end(code, pos)?;
+    };
+    out.extend_from_slice(&code[start..pos]);
+    Some(pos)
+}
+
+// Write an object member's name and the following colon. Returns the position after the colon and any whitespace, or
+// None if it's invalid.
+fn write_member_name(code: &[u8], pos: usize, out: &mut Vec<u8>) -> Option<usize> {
+    let pos = skip_whitespace(code, write_string(code, pos, out)?);
+    if code.get(pos) != Some(&b':') {
+        return None;
+    };
+    out.push(b':');
+    Some(skip_whitespace(code, pos + 1))
+}
+
+// Minify JSON by removing whitespace outside of strings. Returns None if the code isn't valid JSON.
+fn minify_json(code: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::<u8>::with_capacity(code.len());
+    // Open objects and arrays, as their starting characters. This isn't done recursively so that deeply nested code
+    // can't overflow the stack.
+    let mut open = Vec::<u8>::new();
+    let mut pos = skip_whitespace(code, 0);
+    loop {
+        // Expect a value.
+        match *code.get(pos)? {
+            c @ b'{' | c @ b'[' => {
+                out.push(c);
+                pos = skip_whitespace(code, pos + 1);
+                let end = if c == b'{' { b'}' } else { b']' };
+                if code.get(pos) == Some(&end) {
+                    out.push(end);
+                    pos += 1;
+                } else {
+                    open.push(c);
+                    if c == b'{' {
+                        pos = write_member_name(code, pos, &mut out)?;
+                    };
+                    continue;
+                };
+            }
+            b'"' => pos = write_string(code, pos, &mut out)?,
+            b'-' | b'0'..=b'9' => pos = write_number(code, pos, &mut out)?,
+            _ => {
+                let literal = LITERALS.iter().find(|l| code[pos..].starts_with(l))?;
+                out.extend_from_slice(literal);
+                pos += literal.len();
+            }
+        };
+        // Close any objects and arrays that end after the value, until there's another value or the end of the code.
+        loop {
+            pos = skip_whitespace(code, pos);
+            match (open.last(), code.get(pos)) {
+                (None, None) => return Some(out),
+                (Some(o), Some(b',')) => {
+                    out.push(b',');
+                    pos = skip_whitespace(code, pos + 1);
+                    if *o == b'{' {
+                        pos = write_member_name(code, pos, &mut out)?;
+                    };
+                    break;
+                }
+                (Some(b'{'), Some(b'}')) | (Some(b'['), Some(b']')) => {
+                    out.push(code[pos]);
+                    pos += 1;
+                    open.pop();
+                }
+                _ => return None,
+            };
+        };
+    };
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+
+#[cfg(feature = "js-esbuild")]
+use lazy_static::lazy_static;
+
+#[cfg(feature = "css-builtin")]
+pub use crate::minifier::css::BuiltinCssMinifier;
+#[cfg(feature = "js-esbuild")]
+pub use crate::minifier::esbuild::{EsbuildMinifier, EsbuildOptions};
+#[cfg(feature = "js-esbuild")]
+pub use esbuild_rs::Target as EsbuildTarget;
+pub use crate::minifier::cache::MinifyCache;
+pub use crate::minifier::json::BuiltinJsonMinifier;
+
+mod cache;
+#[cfg(feature = "css-builtin")]
+mod css;
+#[cfg(feature = "js-esbuild")]
+mod esbuild;
+mod json;
+
+/// The type of code in a section to minify.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CodeKind {
+    /// JavaScript in a `<script>` tag.
+    Js,
+    /// A JavaScript module in a `<script type="module">` tag. It's always in strict mode, and can
+    /// contain `import` and `export` statements.
+    JsModule,
+    /// The body of a JavaScript function, such as in an event handler attribute like `onclick`.
+    /// It can contain statements like `return`.
+    JsFunctionBody,
+    /// A CSS stylesheet in a `<style>` tag.
+    Css,
+    /// A list of CSS declarations without a selector or braces, such as in a `style` attribute.
+    CssDeclarations,
+    /// JSON, such as in a `<script type="application/ld+json">` tag.
+    Json,
+}
+
+impl fmt::Display for CodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CodeKind::Js => "JS",
+            CodeKind::JsModule => "JS module",
+            CodeKind::JsFunctionBody => "JS function body",
+            CodeKind::Css => "CSS",
+            CodeKind::CssDeclarations => "CSS declarations",
+            CodeKind::Json => "JSON",
+        })
+    }
+}
+
+/// Why code couldn't be minified by a `ContentMinifier`. The original code is kept in either case.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MinifyError {
+    /// The minifier doesn't support this kind of code. This is never reported as a failure.
+    Unsupported,
+    /// The code couldn't be minified, usually because it's invalid. Contains messages describing
+    /// why, such as syntax errors, which are reported according to `Cfg::on_minify_failure`.
+    Failed(Vec<String>),
+}
+
+/// The result of minifying code with a `ContentMinifier`.
+pub type MinifyResult = Result<Vec<u8>, MinifyError>;
+
+/// A minifier for JS, CSS, and JSON code found in HTML, set using `Cfg::minifier`.
+///
+/// Minified code is escaped as necessary (e.g. `</script` in JS, or quotes in attribute values)
+/// before being written, and is only used if it's shorter than the original code.
+pub trait ContentMinifier: Send + Sync {
+    /// Minifies `code`, returning the minified code, or why it couldn't be minified, in which case
+    /// the original code is kept.
+    fn minify(&self, kind: CodeKind, code: &[u8]) -> MinifyResult;
+
+    /// Minifies `code` and calls `done` with the result, which can happen on another thread.
+    /// Minification waits for all `done` callbacks to be called or dropped before finishing,
+    /// so sections can be minified concurrently while the rest of the HTML is processed.
+    ///
+    /// By default, this calls `minify` and then `done` immediately.
+    fn minify_async(&self, kind: CodeKind, code: Vec<u8>, done: Box<dyn FnOnce(MinifyResult) + Send>) -> () {
+        done(self.minify(kind, &code));
+    }
+
+    /// Identifies this minifier and its options, so that a `MinifyCache` doesn't use code that was
+    /// minified differently, such as by another minifier or with other options. Include a version
+    /// if the cache could be stored on disk and the output could change between versions.
+    ///
+    /// By default, this is an empty string.
+    fn cache_key(&self) -> String {
+        String::new()
+    }
+}
+
+#[cfg(feature = "js-esbuild")]
+lazy_static! {
+    static ref DEFAULT_MINIFIER: EsbuildMinifier = EsbuildMinifier::new();
+}
+
+// The minifier to use if `Cfg::minifier` is not set.
+#[cfg(feature = "js-esbuild")]
+pub fn default_minifier() -> Option<&'static dyn ContentMinifier> {
+    Some(&*DEFAULT_MINIFIER)
+}
+
+// The minifier to use if `Cfg::minifier` is not set.
+#[cfg(all(feature = "css-builtin", not(feature = "js-esbuild")))]
+pub fn default_minifier() -> Option<&'static dyn ContentMinifier> {
+    Some(&BuiltinCssMinifier)
+}
+
+// The minifier to use if `Cfg::minifier` is not set.
+#[cfg(not(any(feature = "js-esbuild", feature = "css-builtin")))]
+pub fn default_minifier() -> Option<&'static dyn ContentMinifier> {
+    None
+}
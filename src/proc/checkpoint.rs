@@ -66,4 +66,10 @@ impl ReadCheckpoint {
     pub fn restore(&self, proc: &mut Processor) -> () {
         proc.read_next = self.read_next;
     }
+
+    /// Get read characters since checkpoint as range.
+    #[inline(always)]
+    pub fn read_range(&self, proc: &Processor) -> ProcessorRange {
+        ProcessorRange { start: self.read_next, end: proc.read_next }
+    }
 }
@@ -18,13 +18,15 @@ use std::char::from_u32;
 use crate::gen::codepoints::{ALPHANUMERIC_OR_EQUALS, DIGIT, HEX_DIGIT, Lookup, LOWER_HEX_ALPHA, UPPER_HEX_ALPHA};
 use crate::gen::entities::{ENTITY, EntityType};
 use crate::pattern::TrieNodeMatch;
+use crate::err::ErrorType;
 use crate::proc::Processor;
 
 enum Parsed {
-    // This includes numeric entities that were invalid and decoded to 0xFFFD.
+    // This includes numeric entities that were invalid and decoded to 0xFFFD, which are marked as malformed.
     Decoded {
         read_len: usize,
         write_len: usize,
+        malformed: bool,
     },
     // Some entities are shorter than their decoded UTF-8 sequence. As such, we leave them encoded.
     // Also, named entities that don't end in ';' but are followed by an alphanumeric or `=` char
@@ -64,11 +66,11 @@ fn parse_numeric_entity(code: &mut [u8], read_start: usize, prefix_len: usize, w
     // Browsers decode to a replacement character (U+FFFD) if malformed.
     let char = Some(value)
         .filter(|_| digits <= max_digits)
-        .and_then(|v| from_u32(v))
-        .unwrap_or('\u{FFFD}');
+        .and_then(|v| from_u32(v));
     Parsed::Decoded {
         read_len: read_next - read_start,
-        write_len: char.encode_utf8(&mut code[write_pos..]).len(),
+        write_len: char.unwrap_or('\u{FFFD}').encode_utf8(&mut code[write_pos..]).len(),
+        malformed: char.is_none(),
     }
 }
 
@@ -112,6 +114,7 @@ fn parse_entity(code: &mut [u8], read_pos: usize, write_pos: usize, in_attr_val:
                     Parsed::Decoded {
                         read_len: match_len,
                         write_len: decoded.len(),
+                        malformed: false,
                     }
                 }
             }
@@ -143,14 +146,17 @@ pub fn maybe_normalise_entity(proc: &mut Processor, in_attr_val: bool) -> bool {
             None => break,
             Some(b'&') => {
                 // Decode before checking to see if it continues current entity.
-                let (read_len, write_len) = match parse_entity(proc.code, read_next, write_next, in_attr_val) {
+                let (read_len, write_len) = match parse_entity(&mut proc.code, read_next, write_next, in_attr_val) {
                     Parsed::LeftEncoded => {
                         // Don't mistake an intentionally undecoded entity for an unintentional entity.
                         break;
                     }
-                    Parsed::Decoded { read_len, write_len } => {
+                    Parsed::Decoded { read_len, write_len, malformed } => {
                         debug_assert!(read_len > 0);
                         debug_assert!(write_len > 0);
+                        if malformed && proc.is_lenient() {
+                            proc.warn_at(read_next, ErrorType::InvalidEntity);
+                        };
                         (read_len, write_len)
                     }
                     Parsed::Invalid { len } => {
@@ -207,5 +213,65 @@ pub fn maybe_normalise_entity(proc: &mut Processor, in_attr_val: bool) -> bool {
     };
 
     proc.read_next = shifted_start;
+    proc.decoded_until = read_next;
     return true;
 }
+
+// Get the character the entity at the next position decodes to, if it's a single ASCII character, without decoding or
+// consuming anything.
+pub fn peek_entity_ascii(proc: &Processor) -> Option<u8> {
+    proc.peek(0).filter(|c| *c == b'&')?;
+    let code = &proc.code[proc.read_next..];
+    let numeric = |prefix_len: usize, radix: u32| {
+        let mut value = 0u32;
+        for &c in code[prefix_len..].iter() {
+            match (c as char).to_digit(radix) {
+                Some(digit) => value = value * radix + digit,
+                // Zero decodes to U+FFFD.
+                None => return Some(value as u8).filter(|c| *c != 0),
+            };
+            if value >= 0x80 {
+                return None;
+            };
+        };
+        // The digits could continue if there was more code.
+        proc.reached_end.set(true);
+        Some(value as u8).filter(|c| *c != 0)
+    };
+    match ENTITY.longest_matching_prefix(code) {
+        TrieNodeMatch::Found { value: EntityType::Dec, .. } => numeric(2, 10),
+        TrieNodeMatch::Found { value: EntityType::Hex, .. } => numeric(3, 16),
+        TrieNodeMatch::Found { value: EntityType::Named(decoded), .. } if decoded.len() == 1 => Some(decoded[0]),
+        _ => None,
+    }
+}
+
+// Whether `code` starts with something that could be decoded as an entity.
+pub fn is_entity_start(code: &[u8]) -> bool {
+    match ENTITY.longest_matching_prefix(code) {
+        TrieNodeMatch::Found { .. } => true,
+        TrieNodeMatch::NotFound { .. } => false,
+    }
+}
+
+// Decode all entities in `code` the same way browsers would, e.g. to get the actual value of an attribute.
+pub fn decode_entities(code: &[u8], in_attr_val: bool) -> Vec<u8> {
+    let mut decoded = code.to_vec();
+    let mut read_next = 0;
+    let mut write_next = 0;
+    while read_next < decoded.len() {
+        if decoded[read_next] == b'&' {
+            // Decoded entities are never longer than their encoded form, so this never overwrites unread code.
+            if let Parsed::Decoded { read_len, write_len, .. } = parse_entity(&mut decoded, read_next, write_next, in_attr_val) {
+                read_next += read_len;
+                write_next += write_len;
+                continue;
+            };
+        };
+        decoded[write_next] = decoded[read_next];
+        read_next += 1;
+        write_next += 1;
+    };
+    decoded.truncate(write_next);
+    decoded
+}
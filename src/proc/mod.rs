@@ -1,21 +1,21 @@
 use core::fmt;
 use std::fmt::{Debug, Formatter};
-use std::ops::{Index, IndexMut};
+use std::cell::{Cell, RefCell};
+use std::ops::{Deref, DerefMut, Index, IndexMut};
 
 use aho_corasick::AhoCorasick;
-use memchr::memchr;
+use memchr::{memchr, memmem};
 
-#[cfg(feature = "js-esbuild")]
-use {
-    crossbeam::sync::WaitGroup,
-    std::sync::{Arc, Mutex},
-};
+use std::sync::mpsc::{channel, Receiver, Sender};
 
+use crate::cfg::MinifyFailurePolicy;
 use crate::err::{debug_repr, Error, ErrorType, ProcessingResult};
 use crate::gen::codepoints::Lookup;
+use crate::minifier::{CodeKind, MinifyError};
 use crate::proc::MatchAction::*;
 use crate::proc::MatchMode::*;
 use crate::proc::range::ProcessorRange;
+use crate::source_map::SourceMap;
 
 pub mod checkpoint;
 pub mod entity;
@@ -44,29 +44,154 @@ pub enum MatchMode {
     ThroughSeq(&'static AhoCorasick),
 }
 
+#[derive(Copy, Clone)]
 pub enum MatchAction {
     Keep,
     Discard,
     MatchOnly,
 }
 
-#[cfg(feature = "js-esbuild")]
-pub struct EsbuildSection {
+// Minified code to replace a section of written code with, if shorter, or why it couldn't be minified.
+pub struct MinifiedSection {
     pub src: ProcessorRange,
-    pub escaped: Vec<u8>,
+    pub result: MinifiedSectionResult,
+}
+
+pub enum MinifiedSectionResult {
+    Escaped(Vec<u8>),
+    // The section is kept as is, and the error is recorded as a warning in lenient mode.
+    Warn(Error),
+    // The section is kept as is, and minification fails with the error.
+    Fail(Error),
+}
+
+impl MinifiedSection {
+    // Create the failure to report for the written section `src`, which was at `source_start..source_end` in the source
+    // code, according to `policy`. Returns None if it shouldn't be reported.
+    pub fn failed(
+        src: ProcessorRange,
+        source_start: usize,
+        source_end: usize,
+        kind: CodeKind,
+        err: MinifyError,
+        policy: MinifyFailurePolicy,
+    ) -> Option<MinifiedSection> {
+        let messages = match err {
+            MinifyError::Unsupported => return None,
+            MinifyError::Failed(messages) => messages,
+        };
+        let error = Error {
+            error_type: ErrorType::MinifyFailed { kind, end: source_end, messages },
+            position: source_start,
+        };
+        let result = match policy {
+            MinifyFailurePolicy::Ignore => return None,
+            MinifyFailurePolicy::Warn => MinifiedSectionResult::Warn(error),
+            MinifyFailurePolicy::Fail => MinifiedSectionResult::Fail(error),
+        };
+        Some(MinifiedSection { src, result })
+    }
+}
+
+// The code being processed, which can only be grown if it's a Vec.
+enum Code<'d> {
+    Fixed(&'d mut [u8]),
+    Growable(&'d mut Vec<u8>),
+}
+
+impl<'d> Deref for Code<'d> {
+    type Target = [u8];
+
+    #[inline(always)]
+    fn deref(&self) -> &[u8] {
+        match self {
+            Code::Fixed(code) => code,
+            Code::Growable(code) => code,
+        }
+    }
+}
+
+impl<'d> DerefMut for Code<'d> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            Code::Fixed(code) => code,
+            Code::Growable(code) => code,
+        }
+    }
+}
+
+// Code carried over from suspended processing to the processor that resumes it, like the names of elements that are
+// still open. It's placed before the rest of the source code, and is neither read nor part of the output.
+#[derive(Default)]
+pub struct Context {
+    code: Vec<u8>,
+    // Positions in `code` and where they were in the source code.
+    positions: Vec<(usize, usize)>,
+    // How much of the end of `code` is room for writing.
+    room: usize,
+}
+
+impl Context {
+    #[inline(always)]
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    // Where written code starts when resuming.
+    #[inline(always)]
+    pub fn output_start(&self) -> usize {
+        self.code.len() - self.room
+    }
+
+    // Make room for writing more than has been read when resuming, like pending whitespace and closing tags whose source
+    // code was read before processing was suspended. This must be done last.
+    #[inline(always)]
+    fn reserve(&mut self, len: usize) -> () {
+        self.code.resize(self.code.len() + len, 0);
+        self.room += len;
+    }
 }
 
 // Processing state of a file. Single use only; create one per processing.
 pub struct Processor<'d> {
-    code: &'d mut [u8],
+    code: Code<'d>,
     // Index of the next character to read.
     read_next: usize,
     // Index of the next unwritten space.
     write_next: usize,
-    #[cfg(feature = "js-esbuild")]
-    esbuild_wg: WaitGroup,
-    #[cfg(feature = "js-esbuild")]
-    esbuild_results: Arc<Mutex<Vec<EsbuildSection>>>,
+    // Mapping of written code back to source code, if requested.
+    source_map: Option<SourceMap>,
+    // Whether currently inside the HTML content of a downlevel-hidden conditional comment.
+    in_conditional_comment: bool,
+    // Errors that have been recovered from, if in lenient mode.
+    warnings: Option<Vec<Error>>,
+    // Sections that couldn't be minified with `MinifyFailurePolicy::Warn`, which are recorded in any mode.
+    minify_warnings: Vec<Error>,
+    // Written tag names of elements currently being processed, from outermost to innermost. Only tracked in lenient
+    // mode.
+    open_elements: Vec<ProcessorRange>,
+    // Sections of minified code are sent here once minified, possibly from other threads.
+    minified_send: Sender<MinifiedSection>,
+    minified_recv: Receiver<MinifiedSection>,
+    // For each sequence searched for using `find_seq`, the earliest position from which the source is known to not
+    // contain it.
+    seq_missing_from: RefCell<Vec<usize>>,
+    // Where space has been inserted before unread source code to make room for writing, and how much, in order.
+    gaps: Vec<(usize, usize)>,
+    // Length of any `Context` at the start of the code, and where the code after it starts in the source code.
+    context_len: usize,
+    context_source_start: usize,
+    context_positions: Vec<(usize, usize)>,
+    // Where processing can be suspended if the code could be incomplete. See `suspend_between`.
+    suspend_between: Option<(usize, usize)>,
+    // How many elements currently being processed will be erased, inside which processing can't be suspended.
+    unsuspendable: usize,
+    // Source code before this has been overwritten with decoded entities that haven't all been read yet, so processing
+    // can't be suspended before it as the code is no longer the source code.
+    decoded_until: usize,
+    // Whether the end of the code has been looked at, in which case processing could differ if the code continued.
+    reached_end: Cell<bool>,
 }
 
 impl<'d> Index<ProcessorRange> for Processor<'d> {
@@ -91,22 +216,80 @@ impl<'d> Processor<'d> {
     // Constructor.
     #[inline(always)]
     pub fn new(code: &mut [u8]) -> Processor {
+        Processor::_new(Code::Fixed(code))
+    }
+
+    // Create a processor that can grow `code` if there isn't enough room to write something.
+    #[inline(always)]
+    pub fn new_growable(code: &mut Vec<u8>) -> Processor<'_> {
+        Processor::_new(Code::Growable(code))
+    }
+
+    #[inline(always)]
+    fn _new(code: Code<'d>) -> Processor<'d> {
+        let (minified_send, minified_recv) = channel();
         Processor {
             write_next: 0,
             read_next: 0,
             code,
-            #[cfg(feature = "js-esbuild")]
-            esbuild_wg: WaitGroup::new(),
-            #[cfg(feature = "js-esbuild")]
-            esbuild_results: Arc::new(Mutex::new(Vec::new())),
+            source_map: None,
+            in_conditional_comment: false,
+            warnings: None,
+            minify_warnings: Vec::new(),
+            open_elements: Vec::new(),
+            minified_send,
+            minified_recv,
+            seq_missing_from: RefCell::new(Vec::new()),
+            gaps: Vec::new(),
+            context_len: 0,
+            context_source_start: 0,
+            context_positions: Vec::new(),
+            suspend_between: None,
+            unsuspendable: 0,
+            decoded_until: 0,
+            reached_end: Cell::new(false),
         }
     }
 
+    /// Record a source map of written code back to source code while processing.
+    #[inline(always)]
+    pub fn enable_source_map(&mut self) -> () {
+        self.source_map = Some(SourceMap::new());
+    }
+
+    /// Recover from errors where possible instead of failing, like browsers do.
+    #[inline(always)]
+    pub fn enable_lenient(&mut self) -> () {
+        self.warnings = Some(Vec::new());
+    }
+
     // INTERNAL APIs.
+    // Source mapping.
+    /// Record that code written next is from the next character in source.
+    #[inline(always)]
+    fn _map(&mut self) -> () {
+        let read_next = self.source_position(self.read_next);
+        if let Some(map) = &mut self.source_map {
+            map.record(self.write_next, read_next);
+        };
+    }
+
     // Bounds checking.
     #[inline(always)]
     fn _in_bounds(&self, offset: usize) -> bool {
-        self.read_next + offset < self.code.len()
+        let in_bounds = self.read_next + offset < self.code.len();
+        if !in_bounds {
+            self.reached_end.set(true);
+        };
+        in_bounds
+    }
+
+    #[inline(always)]
+    fn _or_reached_end<T>(&self, found: Option<T>) -> Option<T> {
+        if found.is_none() {
+            self.reached_end.set(true);
+        };
+        found
     }
 
     // Reading.
@@ -120,18 +303,19 @@ impl<'d> Processor<'d> {
 
     #[inline(always)]
     fn _maybe_read_offset(&self, offset: usize) -> Option<u8> {
-        self.code.get(self.read_next + offset).map(|c| *c)
+        self._or_reached_end(self.code.get(self.read_next + offset).map(|c| *c))
     }
 
     #[inline(always)]
     fn _maybe_read_slice_offset(&self, offset: usize, count: usize) -> Option<&[u8]> {
-        self.code.get(self.read_next + offset..self.read_next + offset + count)
+        self._or_reached_end(self.code.get(self.read_next + offset..self.read_next + offset + count))
     }
 
     /// Move next `amount` characters to output.
     /// Panics. Does not check bounds for performance (e.g. already checked).
     #[inline(always)]
     fn _shift(&mut self, amount: usize) -> () {
+        self._map();
         // Optimisation: Don't shift if already there (but still update offsets).
         if self.read_next != self.write_next {
             self.code.copy_within(self.read_next..self.read_next + amount, self.write_next);
@@ -146,6 +330,7 @@ impl<'d> Processor<'d> {
         let added = data.len() - (end - start);
         // Do not allow writing over source.
         debug_assert!(self.write_next + added <= self.read_next);
+        self._map();
         self.code.copy_within(end..self.write_next, end + added);
         self.code[start..start + data.len()].copy_from_slice(data);
         // Don't need to update read_next as only data before it has changed.
@@ -184,8 +369,8 @@ impl<'d> Processor<'d> {
             IsChar(c) => self._one(|n| n == c),
             IsNotChar(c) => self._one(|n| n != c),
             WhileChar(c) => self._many(|n| n == c),
-            WhileNotChar(c) => memchr(c, &self.code[self.read_next..]).unwrap_or(self._remaining()),
-            ThroughChar(c) => memchr(c, &self.code[self.read_next..]).map_or(0, |p| p + 1),
+            WhileNotChar(c) => self._or_reached_end(memchr(c, &self.code[self.read_next..])).unwrap_or(self._remaining()),
+            ThroughChar(c) => self._or_reached_end(memchr(c, &self.code[self.read_next..])).map_or(0, |p| p + 1),
 
             IsInLookup(lookup) => self._one(|n| lookup[n]),
             WhileInLookup(lookup) => self._many(|n| lookup[n]),
@@ -197,9 +382,9 @@ impl<'d> Processor<'d> {
             WhileNotPred(p) => self._many(|n| !p(n)),
 
             IsSeq(seq) => self._maybe_read_slice_offset(0, seq.len()).filter(|src| *src == seq).map_or(0, |_| seq.len()),
-            WhileNotSeq(seq) => seq.find(&self.code[self.read_next..]).map_or(self._remaining(), |m| m.start()),
+            WhileNotSeq(seq) => self._or_reached_end(seq.find(&self.code[self.read_next..])).map_or(self._remaining(), |m| m.start()),
             // Match.end is exclusive, so do not add one.
-            ThroughSeq(seq) => seq.find(&self.code[self.read_next..]).map_or(0, |m| m.end()),
+            ThroughSeq(seq) => self._or_reached_end(seq.find(&self.code[self.read_next..])).map_or(0, |m| m.end()),
         };
         // If keeping, match will be available in written range (which is better as source might eventually get overwritten).
         // If discarding, then only option is source range.
@@ -238,8 +423,29 @@ impl<'d> Processor<'d> {
         self.read_next
     }
 
+    // Get where `pos` was in the source code, which could differ if space has been inserted before it or processing was
+    // resumed.
+    #[inline(always)]
+    pub fn source_position(&self, pos: usize) -> usize {
+        if pos < self.context_len {
+            // Only some positions in the context are recorded, such as where open elements start.
+            return self.context_positions.iter()
+                .find(|(at, _)| *at == pos)
+                .map_or(self.context_source_start, |(_, source)| *source);
+        };
+        pos - self.context_len + self.context_source_start - self.gaps.iter().filter(|(at, _)| *at < pos).map(|(_, len)| len).sum::<usize>()
+    }
+
+    // Get the source code from `start` onwards, which must not have been written over.
+    #[inline(always)]
+    pub fn source_from(&self, start: usize) -> &[u8] {
+        debug_assert!(start >= self.write_next);
+        &self.code[start..]
+    }
+
     #[inline(always)]
     pub fn reserve_output(&mut self, amount: usize) -> () {
+        self._map();
         self.write_next += amount;
     }
 
@@ -256,11 +462,159 @@ impl<'d> Processor<'d> {
         self._maybe_read_slice_offset(offset, count)
     }
 
+    // Find `seq` at or after `offset` from the next character, returning its offset. Searches for the same sequence
+    // with the same `id` are remembered so that they don't search the same source again once it's not found.
+    pub fn find_seq(&self, id: usize, offset: usize, seq: &[u8]) -> Option<usize> {
+        let start = self.read_next + offset;
+        let mut missing_from = self.seq_missing_from.borrow_mut();
+        if missing_from.len() <= id {
+            missing_from.resize(id + 1, usize::MAX);
+        };
+        if start >= missing_from[id] || start > self.code.len() {
+            self.reached_end.set(true);
+            return None;
+        };
+        match memmem::find(&self.code[start..], seq) {
+            Some(pos) => Some(offset + pos),
+            None => {
+                missing_from[id] = start;
+                self.reached_end.set(true);
+                None
+            }
+        }
+    }
+
     // Looking behind.
     pub fn last_is(&self, c: u8) -> bool {
         self.write_next > 0 && self.code[self.write_next - 1] == c
     }
 
+    // Conditional comments.
+    #[inline(always)]
+    pub fn in_conditional_comment(&self) -> bool {
+        self.in_conditional_comment
+    }
+
+    #[inline(always)]
+    pub fn set_in_conditional_comment(&mut self, in_conditional_comment: bool) -> () {
+        self.in_conditional_comment = in_conditional_comment;
+    }
+
+    // Suspending and resuming.
+    // Resume processing code that starts with `context`, followed by the rest of the source code from `source_start`.
+    pub fn resume(&mut self, context: &Context, source_start: usize, in_conditional_comment: bool) -> () {
+        debug_assert!(self.code.starts_with(&context.code));
+        self.read_next = context.code.len();
+        self.write_next = context.output_start();
+        self.context_len = context.code.len();
+        self.context_source_start = source_start;
+        self.context_positions = context.positions.clone();
+        self.in_conditional_comment = in_conditional_comment;
+    }
+
+    // Suspend processing at the first element in content that starts between `from` and `to`, as the code could be
+    // incomplete. Looking ahead from anywhere before `to` must never need to reach the end.
+    #[inline(always)]
+    pub fn suspend_between(&mut self, from: usize, to: usize) -> () {
+        self.suspend_between = Some((from, to));
+    }
+
+    // Whether processing should be suspended before the next character.
+    #[inline(always)]
+    pub fn should_suspend(&self) -> bool {
+        self.unsuspendable == 0
+            && self.read_next >= self.decoded_until
+            && self.suspend_between.filter(|(from, to)| (*from..=*to).contains(&self.read_next)).is_some()
+    }
+
+    #[inline(always)]
+    pub fn set_suspendable(&mut self, suspendable: bool) -> () {
+        if suspendable {
+            self.unsuspendable -= 1;
+        } else {
+            self.unsuspendable += 1;
+        };
+    }
+
+    // Whether anything looked at the end of the code.
+    #[inline(always)]
+    pub fn reached_end(&self) -> bool {
+        self.reached_end.get()
+    }
+
+    // Copy the written code in `range` to the end of `context`, returning where it will be when resuming.
+    pub fn export_range(&self, range: ProcessorRange, context: &mut Context) -> ProcessorRange {
+        let start = context.code.len();
+        context.code.extend_from_slice(&self[range]);
+        ProcessorRange { start, end: context.code.len() }
+    }
+
+    // Add the position `pos` to the end of `context`, returning where it will be when resuming.
+    pub fn export_position(&self, pos: usize, context: &mut Context) -> usize {
+        let at = context.code.len();
+        // Nothing reads the context at a position, so any character will do.
+        context.code.push(b'<');
+        context.positions.push((at, self.source_position(pos)));
+        at
+    }
+
+    // Make room for writing up to `len` more than has been read when resuming, but no more than there's room for now.
+    pub fn export_room(&self, len: usize, context: &mut Context) -> () {
+        context.reserve(len.min(self.read_next - self.write_next));
+    }
+
+    // Copy the last written character to the end of `context`, so that it can still be looked behind at when resuming.
+    pub fn export_last_written(&self, context: &mut Context) -> () {
+        if self.write_next > 0 {
+            context.code.push(self.code[self.write_next - 1]);
+        };
+    }
+
+    // Lenient mode.
+    #[inline(always)]
+    pub fn is_lenient(&self) -> bool {
+        self.warnings.is_some()
+    }
+
+    /// Record an error that has been recovered from at the current position.
+    #[inline(always)]
+    pub fn warn(&mut self, error_type: ErrorType) -> () {
+        self.warn_at(self.read_next, error_type);
+    }
+
+    /// Record an error that has been recovered from at a position in the source code.
+    #[inline(always)]
+    pub fn warn_at(&mut self, position: usize, error_type: ErrorType) -> () {
+        let position = self.source_position(position);
+        self.warnings.as_mut().unwrap().push(Error { error_type, position });
+    }
+
+    // Take errors that have been recovered from in lenient mode, followed by any minification failures to warn about.
+    #[inline(always)]
+    pub fn take_warnings(&mut self) -> Vec<Error> {
+        let mut warnings = self.warnings.take().unwrap_or_default();
+        warnings.append(&mut self.minify_warnings);
+        warnings
+    }
+
+    #[inline(always)]
+    pub fn push_open_element(&mut self, name: ProcessorRange) -> () {
+        if self.is_lenient() {
+            self.open_elements.push(name);
+        };
+    }
+
+    #[inline(always)]
+    pub fn pop_open_element(&mut self) -> () {
+        self.open_elements.pop();
+    }
+
+    /// Whether an element with this (lowercase) tag name is currently being processed.
+    #[inline(always)]
+    pub fn is_open_element(&self, name: &[u8]) -> bool {
+        self.open_elements.iter().any(|r| &self[*r] == name)
+    }
+
     // Consuming source characters.
     /// Skip and return the next character.
     /// Will result in an error if exceeds bounds.
@@ -288,6 +642,7 @@ impl<'d> Processor<'d> {
     /// Write `c` to output. Will panic if exceeds bounds.
     #[inline(always)]
     pub fn write(&mut self, c: u8) -> () {
+        self._map();
         self.code[self.write_next] = c;
         self.write_next += 1;
     }
@@ -305,14 +660,51 @@ impl<'d> Processor<'d> {
     pub fn write_range(&mut self, s: ProcessorRange) -> ProcessorRange {
         let dest_start = self.write_next;
         let dest_end = dest_start + s.len();
+        // Range could be from source (e.g. previously discarded) or already written code (e.g. closing tag name).
+        let source_start = self.source_position(if s.start >= dest_start { s.start } else { self.read_next });
+        if let Some(map) = &mut self.source_map {
+            map.record(dest_start, source_start);
+        };
         self.code.copy_within(s.start..s.end, dest_start);
         self.write_next = dest_end;
         ProcessorRange { start: dest_start, end: dest_end }
     }
 
+    // Whether `len` characters can be written without overwriting source code that hasn't been read yet.
+    #[inline(always)]
+    pub fn can_write(&self, len: usize) -> bool {
+        self.write_next + len <= self.read_next
+    }
+
+    // Make sure `len` characters can be written without overwriting source code that hasn't been read yet, by moving the
+    // unread source code along if the code can be grown.
+    pub fn make_room(&mut self, len: usize) -> ProcessingResult<()> {
+        if self.can_write(len) {
+            return Ok(());
+        };
+        let code = match &mut self.code {
+            Code::Growable(code) => code,
+            Code::Fixed(_) => return Err(ErrorType::ReplacementTooLong),
+        };
+        // Make more room than needed so that many long replacements don't each move all of the remaining source code.
+        let gap = (self.write_next + len - self.read_next).max(code.len() / 8);
+        let end = code.len();
+        code.resize(end + gap, 0);
+        code.copy_within(self.read_next..end, self.read_next + gap);
+        for missing_from in self.seq_missing_from.get_mut().iter_mut().filter(|p| **p != usize::MAX) {
+            if *missing_from >= self.read_next {
+                *missing_from += gap;
+            };
+        };
+        self.gaps.push((self.read_next, gap));
+        self.read_next += gap;
+        Ok(())
+    }
+
     /// Write `s` to output. Will panic if exceeds bounds.
     #[inline(always)]
     pub fn write_slice(&mut self, s: &[u8]) -> () {
+        self._map();
         self.code[self.write_next..self.write_next + s.len()].copy_from_slice(s);
         self.write_next += s.len();
     }
@@ -326,6 +718,7 @@ impl<'d> Processor<'d> {
     // Shifting characters.
     #[inline(always)]
     pub fn accept(&mut self) -> ProcessingResult<u8> {
+        self._map();
         self._maybe_read_offset(0).map(|c| {
             self.code[self.write_next] = c;
             self.read_next += 1;
@@ -337,6 +730,7 @@ impl<'d> Processor<'d> {
     #[inline(always)]
     pub fn accept_expect(&mut self) -> u8 {
         debug_assert!(!self.at_end());
+        self._map();
         let c = self._read_offset(0);
         self.code[self.write_next] = c;
         self.read_next += 1;
@@ -350,37 +744,61 @@ impl<'d> Processor<'d> {
         self._shift(count);
     }
 
-    #[cfg(feature = "js-esbuild")]
+    // Get a sender for a section of minified code, which will replace the section when finishing if shorter. Dropping
+    // the sender without sending anything leaves the section as is.
     #[inline(always)]
-    pub fn new_esbuild_section(&self) -> (WaitGroup, Arc<Mutex<Vec<EsbuildSection>>>) {
-        (self.esbuild_wg.clone(), self.esbuild_results.clone())
+    pub fn new_minified_section(&self) -> Sender<MinifiedSection> {
+        self.minified_send.clone()
     }
 
-    // Since we consume the Processor, we must provide a full Error with positions.
-    #[cfg(not(feature = "js-esbuild"))]
+    // Get `error` with positions in the source code.
+    pub fn source_error(&self, Error { error_type, position }: Error) -> Error {
+        let error_type = match error_type {
+            ErrorType::MinifyFailed { kind, end, messages } => ErrorType::MinifyFailed { kind, end: self.source_position(end), messages },
+            ErrorType::NotFound { expected, construct, start } => ErrorType::NotFound { expected, construct, start: self.source_position(start) },
+            error_type => error_type,
+        };
+        Error { error_type, position: self.source_position(position) }
+    }
+
+    // This must only be called once, after which only warnings can be taken. As processing has finished, we must
+    // provide a full Error with positions.
     #[inline(always)]
-    pub fn finish(self) -> Result<usize, Error> {
-        debug_assert!(self.at_end());
-        Ok(self.write_next)
+    pub fn finish(&mut self) -> Result<usize, Error> {
+        self.finish_with_source_map().map(|(len, _)| len)
     }
 
-    // Since we consume the Processor, we must provide a full Error with positions.
-    #[cfg(feature = "js-esbuild")]
+    // This must only be called once, after which only warnings can be taken. As processing has finished, we must
+    // provide a full Error with positions.
     #[inline(always)]
-    pub fn finish(self) -> Result<usize, Error> {
-        debug_assert!(self.at_end());
-        self.esbuild_wg.wait();
-        let mut results = Arc::try_unwrap(self.esbuild_results)
-            .unwrap_or_else(|_| panic!("failed to acquire esbuild results"))
-            .into_inner()
-            .unwrap();
-        results.sort_unstable_by_key(|r| r.src.start);
+    pub fn finish_with_source_map(&mut self) -> Result<(usize, Option<SourceMap>), Error> {
+        debug_assert!(self.at_end() || self.suspend_between.is_some());
+        let mut source_map = self.source_map.take();
+        // Wait for all sections to be minified, which is when all senders have been dropped.
+        drop(std::mem::replace(&mut self.minified_send, channel().0));
+        let mut results = Vec::<(ProcessorRange, Vec<u8>)>::new();
+        let mut failures = Vec::<(Error, bool)>::new();
+        for MinifiedSection { src, result } in self.minified_recv.iter() {
+            match result {
+                MinifiedSectionResult::Escaped(min_code) => results.push((src, min_code)),
+                MinifiedSectionResult::Warn(error) => failures.push((self.source_error(error), false)),
+                MinifiedSectionResult::Fail(error) => failures.push((self.source_error(error), true)),
+            };
+        };
+        failures.sort_unstable_by_key(|(error, _)| error.position);
+        for (error, fatal) in failures {
+            if fatal {
+                return Err(error);
+            };
+            self.minify_warnings.push(error);
+        };
+        results.sort_unstable_by_key(|(src, _)| src.start);
         // As we write minified JS/CSS code for sections from left to right, we will be shifting code
         // towards the left as previous source JS/CSS code sections shrink. We need to keep track of
         // the write pointer after previous compaction.
         // If there are no script sections, then we get self.write_next which will be returned.
-        let mut write_next = results.get(0).map_or(self.write_next, |r| r.src.start);
-        for (i, EsbuildSection { escaped: min_code, src }) in results.iter().enumerate() {
+        let mut write_next = results.get(0).map_or(self.write_next, |(src, _)| src.start);
+        for (i, (src, min_code)) in results.iter().enumerate() {
             // Resulting minified JS/CSS to write.
             let min_len = if min_code.len() < src.len() {
                 self.code[write_next..write_next + min_code.len()].copy_from_slice(min_code);
@@ -392,17 +810,25 @@ impl<'d> Processor<'d> {
                 src.len()
             };
             let write_end = write_next + min_len;
-            let next_start = results.get(i + 1).map_or(self.write_next, |r| r.src.start);
+            let next_start = results.get(i + 1).map_or(self.write_next, |(src, _)| src.start);
             self.code.copy_within(src.end..next_start, write_end);
             write_next = write_end + (next_start - src.end);
         };
-        Ok(write_next)
+        if let Some(map) = &mut source_map {
+            // Replace sections from right to left so that positions of sections not yet replaced are still correct.
+            for (src, min_code) in results.iter().rev() {
+                if min_code.len() < src.len() {
+                    map.replace(src.start, src.end, min_code.len());
+                };
+            };
+        };
+        Ok((write_next, source_map))
     }
 }
 
 impl Debug for Processor<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.write_str(&debug_repr(self.code, self.read_next as isize, self.write_next as isize))?;
+        f.write_str(&debug_repr(&self.code, self.read_next as isize, self.write_next as isize))?;
         Ok(())
     }
 }
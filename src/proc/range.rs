@@ -34,9 +34,9 @@ impl ProcessorRange {
     }
 
     #[inline(always)]
-    pub fn require(&self, reason: &'static str) -> ProcessingResult<Self> {
+    pub fn require(&self, expected: &'static str, construct: &'static str, start: usize) -> ProcessingResult<Self> {
         if self.empty() {
-            Err(ErrorType::NotFound(reason))
+            Err(ErrorType::NotFound { expected, construct, start })
         } else {
             Ok(*self)
         }
@@ -0,0 +1,212 @@
+use memchr::memchr;
+
+// Mappings are stored as a compact offset table of (minified position, source position) pairs, sorted by minified
+// position. Each pair marks the start of a run of minified code that maps linearly to the source code starting at
+// the paired source position, until the next pair.
+
+/// Mapping from positions in minified code back to positions in the original source code.
+///
+/// The mapping is a compact offset table; use `to_json` to generate a
+/// [Source Map v3](https://sourcemaps.info/spec.html) document from it.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    mappings: Vec<(usize, usize)>,
+}
+
+impl SourceMap {
+    pub(crate) fn new() -> SourceMap {
+        SourceMap { mappings: Vec::new() }
+    }
+
+    /// Record that minified code written from `min_pos` onwards corresponds to source code from `src_pos` onwards.
+    #[inline(always)]
+    pub(crate) fn record(&mut self, min_pos: usize, src_pos: usize) -> () {
+        // Minified code at or after `min_pos` may have been discarded and is now being overwritten.
+        while self.mappings.last().filter(|(m, _)| *m >= min_pos).is_some() {
+            self.mappings.pop();
+        };
+        if self.mappings.last().filter(|(m, s)| s.wrapping_sub(*m) == src_pos.wrapping_sub(min_pos)).is_none() {
+            self.mappings.push((min_pos, src_pos));
+        };
+    }
+
+    /// Replace the minified code in `start..end` with `len` bytes that cannot be mapped any more precisely than to
+    /// the start of the replaced code, shifting mappings for all code after it.
+    pub(crate) fn replace(&mut self, start: usize, end: usize, len: usize) -> () {
+        if end - start == len {
+            return;
+        };
+        let src_start = self.source_position(start);
+        let src_end = self.source_position(end);
+        let removed = end - start - len;
+        let mut mappings = Vec::<(usize, usize)>::with_capacity(self.mappings.len() + 2);
+        // If there are multiple mappings for the same minified position, the last one wins.
+        let mut push = |m: usize, s: usize| match mappings.last_mut() {
+            Some(last) if last.0 == m => *last = (m, s),
+            _ => mappings.push((m, s)),
+        };
+        for &(m, s) in self.mappings.iter().filter(|(m, _)| *m < start) {
+            push(m, s);
+        };
+        push(start, src_start);
+        push(start + len, src_end);
+        for &(m, s) in self.mappings.iter().filter(|(m, _)| *m >= end) {
+            push(m - removed, s);
+        };
+        self.mappings = mappings;
+    }
+
+    /// Get the compact offset table of (minified position, source position) pairs, sorted by minified position.
+    /// Minified code from each minified position up to the next one maps linearly to the source code starting at
+    /// the paired source position.
+    pub fn mappings(&self) -> &[(usize, usize)] {
+        &self.mappings
+    }
+
+    /// Get the position in the source code that the byte at `min_pos` in the minified code came from.
+    pub fn source_position(&self, min_pos: usize) -> usize {
+        match self.mappings.binary_search_by_key(&min_pos, |(m, _)| *m) {
+            Ok(i) => self.mappings[i].1,
+            Err(0) => min_pos,
+            Err(i) => {
+                let (m, s) = self.mappings[i - 1];
+                s + (min_pos - m)
+            }
+        }
+    }
+
+    /// Generates a Source Map v3 JSON document. Columns are counted in UTF-16 code units.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The original source code, before minification.
+    /// * `minified` - The minified code.
+    /// * `source_name` - The name or URL of the source file, as it should appear in the `sources` field.
+    pub fn to_json(&self, source: &[u8], minified: &[u8], source_name: &str) -> String {
+        let mut src_cursor = LineCursor::new(source);
+        let mut min_cursor = LineCursor::new(minified);
+        let mut mappings = String::new();
+        let mut min_line = 0;
+        // Fields of the previous segment, as each segment is encoded relative to the previous one.
+        let mut prev_min_col = 0isize;
+        let mut prev_src_line = 0isize;
+        let mut prev_src_col = 0isize;
+        let mut segment = |mappings: &mut String, min_pos: usize, src_pos: usize| {
+            let (line, col) = min_cursor.position(min_pos);
+            let (src_line, src_col) = src_cursor.position(src_pos.min(source.len()));
+            if line > min_line {
+                for _ in min_line..line {
+                    mappings.push(';');
+                };
+                min_line = line;
+                prev_min_col = 0;
+            } else if !mappings.is_empty() && !mappings.ends_with(';') {
+                mappings.push(',');
+            };
+            encode_vlq(mappings, col as isize - prev_min_col);
+            encode_vlq(mappings, 0);
+            encode_vlq(mappings, src_line as isize - prev_src_line);
+            encode_vlq(mappings, src_col as isize - prev_src_col);
+            prev_min_col = col as isize;
+            prev_src_line = src_line as isize;
+            prev_src_col = src_col as isize;
+        };
+        for (i, &(min_pos, src_pos)) in self.mappings.iter().enumerate() {
+            if min_pos >= minified.len() {
+                break;
+            };
+            segment(&mut mappings, min_pos, src_pos);
+            // Add mappings for the start of every line within this run of minified code, as otherwise those lines
+            // won't have any mappings.
+            let run_end = self.mappings.get(i + 1).map_or(minified.len(), |(m, _)| *m).min(minified.len());
+            let mut pos = min_pos;
+            while let Some(nl) = memchr(b'\n', &minified[pos..run_end]) {
+                pos += nl + 1;
+                if pos >= run_end {
+                    break;
+                };
+                segment(&mut mappings, pos, src_pos + (pos - min_pos));
+            };
+        };
+
+        let mut json = String::from("{\"version\":3,\"sources\":[");
+        push_json_string(&mut json, source_name);
+        json.push_str("],\"names\":[],\"mappings\":\"");
+        json.push_str(&mappings);
+        json.push_str("\"}");
+        json
+    }
+}
+
+// Calculates zero-based line and UTF-16 column numbers of positions in some code. Calculating positions in
+// ascending order is fast, as it continues from the previously calculated position.
+struct LineCursor<'c> {
+    code: &'c [u8],
+    pos: usize,
+    line: usize,
+    line_start: usize,
+    col: usize,
+}
+
+impl<'c> LineCursor<'c> {
+    fn new(code: &'c [u8]) -> LineCursor<'c> {
+        LineCursor { code, pos: 0, line: 0, line_start: 0, col: 0 }
+    }
+
+    fn position(&mut self, pos: usize) -> (usize, usize) {
+        if pos < self.line_start {
+            // Positions aren't always ascending, so go back to start if necessary.
+            self.pos = 0;
+            self.line = 0;
+            self.line_start = 0;
+            self.col = 0;
+        } else if pos < self.pos {
+            self.pos = self.line_start;
+            self.col = 0;
+        };
+        while self.pos < pos {
+            let c = self.code[self.pos];
+            if c == b'\n' {
+                self.line += 1;
+                self.line_start = self.pos + 1;
+                self.col = 0;
+            } else if c & 0xC0 != 0x80 {
+                // Count UTF-8 leading bytes only; code points outside the BMP take two UTF-16 code units.
+                self.col += if c >= 0xF0 { 2 } else { 1 };
+            };
+            self.pos += 1;
+        };
+        (self.line, self.col)
+    }
+}
+
+const BASE64_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_vlq(out: &mut String, value: isize) -> () {
+    let mut vlq = if value < 0 { ((-value) << 1) | 1 } else { value << 1 } as usize;
+    loop {
+        let mut digit = vlq & 0x1F;
+        vlq >>= 5;
+        if vlq > 0 {
+            // Set continuation bit.
+            digit |= 0x20;
+        };
+        out.push(BASE64_CHARS[digit] as char);
+        if vlq == 0 {
+            break;
+        };
+    };
+}
+
+fn push_json_string(out: &mut String, s: &str) -> () {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        };
+    };
+    out.push('"');
+}
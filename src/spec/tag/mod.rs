@@ -1,4 +1,5 @@
 pub mod ns;
 pub mod omission;
+pub mod text;
 pub mod void;
 pub mod whitespace;
@@ -1,4 +1,5 @@
-#[derive(Copy, Clone, PartialEq, Eq)]
+/// The namespace of an element, which affects how its attributes and content are handled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Namespace {
     Html,
     Svg,
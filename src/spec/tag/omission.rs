@@ -269,24 +269,36 @@ lazy_static! {
     };
 }
 
+// Tag names must be lowercase.
 #[inline(always)]
-pub fn can_omit_as_last_node(proc: &Processor, parent: Option<ProcessorRange>, child: ProcessorRange) -> bool {
-    CLOSING_TAG_OMISSION_RULES.get(&proc[child])
+pub fn can_omit_name_as_last_node(parent: Option<&[u8]>, child: &[u8]) -> bool {
+    CLOSING_TAG_OMISSION_RULES.get(child)
         .filter(|r| match &r.is_last {
             ClosingTagOmissionRuleIfLast::Always => true,
             ClosingTagOmissionRuleIfLast::Never => false,
             ClosingTagOmissionRuleIfLast::ParentIsNot(parents) => match parent {
-                Some(tag) => !parents.contains(&proc[tag]),
+                Some(tag) => !parents.contains(tag),
                 None => true,
             },
         })
         .is_some()
 }
 
+// Tag names must be lowercase.
 #[inline(always)]
-pub fn can_omit_as_before(proc: &Processor, before: Option<ProcessorRange>, after: ProcessorRange) -> bool {
+pub fn can_omit_name_as_before(before: Option<&[u8]>, after: &[u8]) -> bool {
     before
-        .and_then(|b| CLOSING_TAG_OMISSION_RULES.get(&proc[b]))
-        .filter(|r| r.followed_by.contains(&proc[after]))
+        .and_then(|b| CLOSING_TAG_OMISSION_RULES.get(b))
+        .filter(|r| r.followed_by.contains(after))
         .is_some()
 }
+
+#[inline(always)]
+pub fn can_omit_as_last_node(proc: &Processor, parent: Option<ProcessorRange>, child: ProcessorRange) -> bool {
+    can_omit_name_as_last_node(parent.map(|p| &proc[p]), &proc[child])
+}
+
+#[inline(always)]
+pub fn can_omit_as_before(proc: &Processor, before: Option<ProcessorRange>, after: ProcessorRange) -> bool {
+    can_omit_name_as_before(before.map(|b| &proc[b]), &proc[after])
+}
@@ -0,0 +1,30 @@
+use crate::spec::tag::ns::Namespace;
+
+// How the content of an element is parsed when it isn't HTML.
+#[derive(Copy, Clone)]
+pub enum TextContent {
+    Script,
+    Style,
+    // Contains the end tag start sequence, e.g. `</textarea`.
+    RcData(&'static [u8]),
+    RawText(&'static [u8]),
+    // There is no end tag; everything until the end of the code is text.
+    PlainText,
+}
+
+// Get how the content of the element with the lowercase `name` in `ns` is parsed, or None if it's HTML.
+pub fn get_text_content(ns: Namespace, name: &[u8]) -> Option<TextContent> {
+    match name {
+        b"script" => Some(TextContent::Script),
+        b"style" => Some(TextContent::Style),
+        _ if ns != Namespace::Html => None,
+        b"textarea" => Some(TextContent::RcData(b"</textarea")),
+        b"title" => Some(TextContent::RcData(b"</title")),
+        b"iframe" => Some(TextContent::RawText(b"</iframe")),
+        b"noembed" => Some(TextContent::RawText(b"</noembed")),
+        b"noframes" => Some(TextContent::RawText(b"</noframes")),
+        b"xmp" => Some(TextContent::RawText(b"</xmp")),
+        b"plaintext" => Some(TextContent::PlainText),
+        _ => None,
+    }
+}
@@ -1,5 +1,7 @@
 use lazy_static::lazy_static;
 use std::collections::HashMap;
+use crate::cfg::Cfg;
+use crate::gen::codepoints::WHITESPACE;
 
 pub struct WhitespaceMinification {
     pub collapse: bool,
@@ -49,6 +51,38 @@ static DEFAULT: &WhitespaceMinification = &WhitespaceMinification {
     trim: false,
 };
 
+/// A group of elements whose content has whitespace minified the same way. See the
+/// [README](https://github.com/wilsonzlin/minify-html#element-types) for details on each group.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WhitespaceGroup {
+    /// Elements like `p` and `h1` that contain formatting elements and text.
+    Content,
+    /// Elements like `li` and `label` that are like content elements but could contain a single
+    /// layout element.
+    ContentFirst,
+    /// Elements like `a` and `strong` that wrap around part of some text.
+    Formatting,
+    /// Elements like `div` and `ul` that contain layout and content elements.
+    Layout,
+    /// Elements like `pre` whose whitespace is left as is, including in their descendants.
+    WhitespaceSensitive,
+    /// Elements not in any other group, whose whitespace is only collapsed.
+    Other,
+}
+
+impl WhitespaceGroup {
+    fn minification(self) -> &'static WhitespaceMinification {
+        match self {
+            WhitespaceGroup::Content => CONTENT,
+            WhitespaceGroup::ContentFirst => CONTENT_FIRST,
+            WhitespaceGroup::Formatting => FORMATTING,
+            WhitespaceGroup::Layout => LAYOUT,
+            WhitespaceGroup::WhitespaceSensitive => WHITESPACE_SENSITIVE,
+            WhitespaceGroup::Other => DEFAULT,
+        }
+    }
+}
+
 lazy_static! {
     static ref TAG_WHITESPACE_MINIFICATION: HashMap<&'static [u8], &'static WhitespaceMinification> = {
         let mut m = HashMap::<&'static [u8], &'static WhitespaceMinification>::new();
@@ -165,13 +199,41 @@ lazy_static! {
     };
 }
 
+fn trim(s: &[u8]) -> &[u8] {
+    let start = s.iter().position(|c| !WHITESPACE[*c]).unwrap_or(s.len());
+    let end = s.iter().rposition(|c| !WHITESPACE[*c]).map_or(start, |p| p + 1);
+    &s[start..end]
+}
+
+// Whether an attribute with this lowercase name and decoded value makes whitespace in an element's content significant
+// when rendered, like `<pre>`.
+pub fn is_whitespace_sensitive_attr(name: &[u8], value: Option<&[u8]>) -> bool {
+    match (name, value) {
+        // An empty value is the same as `true`.
+        (b"contenteditable", value) => !value.unwrap_or(b"").eq_ignore_ascii_case(b"false"),
+        (b"style", Some(value)) => value.split(|c| *c == b';').any(|decl| {
+            let mut parts = decl.splitn(2, |c| *c == b':');
+            let prop = trim(parts.next().unwrap());
+            let val = trim(parts.next().unwrap_or(b""));
+            let val = trim(val.strip_suffix(b"!important").unwrap_or(val));
+            prop.eq_ignore_ascii_case(b"white-space")
+                && [&b"pre"[..], b"pre-wrap", b"pre-line", b"break-spaces"].iter().any(|v| val.eq_ignore_ascii_case(v))
+        }),
+        _ => false,
+    }
+}
+
 #[inline(always)]
-pub fn get_whitespace_minification_for_tag(tag_name: Option<&[u8]>, descendant_of_pre: bool) -> &'static WhitespaceMinification {
+pub fn get_whitespace_minification_for_tag(cfg: &Cfg, tag_name: Option<&[u8]>, descendant_of_pre: bool) -> &'static WhitespaceMinification {
     if descendant_of_pre {
         WHITESPACE_SENSITIVE
     } else {
         match tag_name {
-            Some(n) => TAG_WHITESPACE_MINIFICATION.get(n).unwrap_or(&DEFAULT),
+            // Groups in the configuration override the built-in ones.
+            Some(n) => match cfg.whitespace_groups.get(n) {
+                Some(group) => group.minification(),
+                None => TAG_WHITESPACE_MINIFICATION.get(n).unwrap_or(&DEFAULT),
+            },
             None => ROOT,
         }
     }
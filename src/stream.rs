@@ -0,0 +1,99 @@
+use std::io::{self, Read, Write};
+
+use crate::cfg::Cfg;
+use crate::err::Error;
+use crate::proc::{Context, Processor};
+use crate::resume_document;
+use crate::unit::content::Frame;
+
+// How far processing can look ahead past what it has read, such as when matching an entity, whose name is at most 32
+// characters. Processing is only suspended at least this far from the end of the code read so far, so looking ahead
+// from before then never depends on code that hasn't been read yet. Anything that looks further sets `reached_end`.
+const LOOKAHEAD: usize = 64;
+const READ_SIZE: usize = 8192;
+// How much room to keep for writing more than has been read when resuming, besides what's needed for pending whitespace
+// and closing tags. Writing can get a little ahead of reading in malformed code, like an attribute directly after a
+// tag name, which uses any room left by code read but not written before it.
+const EXTRA_ROOM: usize = 64;
+
+fn invalid_data(err: Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+// Minify code read from `input` in chunks, writing minified code to `output` once it doesn't depend on code that hasn't
+// been read yet. See `stream`.
+//
+// Once enough code has been read, it's processed until the first element that starts in the second half of it, where
+// processing is suspended and everything before it is written. If processing looked at the end of the code before then,
+// or couldn't be suspended, the result could differ once more code is read, so it's discarded and retried once twice as
+// much code has been read. Processing is resumed with the code after where it was suspended, preceded by a `Context`
+// containing the names of open elements and pending closing tags that it refers to.
+pub fn minify_chunks<R: Read, W: Write>(input: &mut R, output: &mut W, cfg: &Cfg) -> io::Result<()> {
+    // The context followed by source code that hasn't been minified yet.
+    let mut code = Vec::<u8>::new();
+    let mut context = Context::default();
+    // Where the source code in `code` starts in the entire source code.
+    let mut source_start = 0;
+    let mut in_conditional_comment = false;
+    let mut frames = vec![Frame::document()];
+    // How much source code should be read before trying to process it.
+    let mut min_len = 2 * LOOKAHEAD;
+    // Processing writes over the source code, which is still needed if processing has to be retried.
+    let mut attempt = Vec::<u8>::new();
+    let mut chunk = vec![0u8; READ_SIZE];
+    loop {
+        let read = match input.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        code.extend_from_slice(&chunk[..read]);
+        let len = code.len() - context.code().len();
+        if len < min_len {
+            continue;
+        };
+
+        attempt.clear();
+        attempt.extend_from_slice(&code);
+        let mut proc = Processor::new_growable(&mut attempt);
+        proc.resume(&context, source_start, in_conditional_comment);
+        proc.suspend_between(context.code().len() + len / 2, code.len() - LOOKAHEAD);
+        let suspended = match resume_document(&mut proc, cfg, frames.clone()) {
+            Ok(Some(suspended)) if !proc.reached_end() => suspended,
+            // The error happened before anything depending on code that hasn't been read yet.
+            Err(err) if !proc.reached_end() && err.position + LOOKAHEAD <= source_start + len => return Err(invalid_data(err)),
+            _ => {
+                min_len = 2 * len;
+                continue;
+            }
+        };
+        let resume_from = proc.source_position(proc.read_len());
+        let mut next_context = Context::default();
+        let pending_len = suspended[0].pending_len();
+        let next_frames = suspended.into_iter().map(|frame| frame.export(&proc, &mut next_context)).collect();
+        proc.export_last_written(&mut next_context);
+        proc.export_room(pending_len + EXTRA_ROOM, &mut next_context);
+        in_conditional_comment = proc.in_conditional_comment();
+        let min_end = proc.finish().map_err(invalid_data)?;
+        drop(proc);
+        output.write_all(&attempt[context.output_start()..min_end])?;
+
+        let rest = &code[context.code().len() + resume_from - source_start..];
+        min_len = 2 * rest.len();
+        attempt.clear();
+        attempt.extend_from_slice(next_context.code());
+        attempt.extend_from_slice(rest);
+        std::mem::swap(&mut code, &mut attempt);
+        context = next_context;
+        frames = next_frames;
+        source_start = resume_from;
+    };
+
+    let mut proc = Processor::new_growable(&mut code);
+    proc.resume(&context, source_start, in_conditional_comment);
+    resume_document(&mut proc, cfg, frames).map_err(invalid_data)?;
+    let min_end = proc.finish().map_err(invalid_data)?;
+    drop(proc);
+    output.write_all(&code[context.output_start()..min_end])
+}
@@ -18,6 +18,12 @@ fn _eval(src: &'static [u8], expected: &'static [u8], cfg: &super::Cfg) -> () {
     };
 }
 
+#[cfg(test)]
+fn _eval_copy(src: &'static [u8], expected: &'static [u8], cfg: &super::Cfg) -> () {
+    let min = super::copy(src, cfg).unwrap();
+    assert_eq!(std::str::from_utf8(&min).unwrap(), std::str::from_utf8(expected).unwrap());
+}
+
 #[cfg(test)]
 fn _eval_error(src: &'static [u8], expected: ErrorType, cfg: &super::Cfg) -> () {
     let mut code = src.to_vec();
@@ -29,6 +35,7 @@ fn eval(src: &'static [u8], expected: &'static [u8]) -> () {
     _eval(src, expected, &super::Cfg {
         minify_js: false,
         minify_css: false,
+        ..super::Cfg::default()
     });
 }
 
@@ -37,6 +44,7 @@ fn eval_error(src: &'static [u8], expected: ErrorType) -> () {
     _eval_error(src, expected, &super::Cfg {
         minify_js: false,
         minify_css: false,
+        ..super::Cfg::default()
     });
 }
 
@@ -46,6 +54,7 @@ fn eval_with_js_min(src: &'static [u8], expected: &'static [u8]) -> () {
     _eval(src, expected, &super::Cfg {
         minify_js: true,
         minify_css: false,
+        ..super::Cfg::default()
     });
 }
 
@@ -55,6 +64,7 @@ fn eval_with_css_min(src: &'static [u8], expected: &'static [u8]) -> () {
     _eval(src, expected, &super::Cfg {
         minify_js: false,
         minify_css: true,
+        ..super::Cfg::default()
     });
 }
 
@@ -466,3 +476,1036 @@ fn test_js_minification_unintentional_closing_tag() {
 fn test_css_minification() {
     eval_with_css_min(b"<style>div { color: yellow }</style>", b"<style>div{color:#ff0}</style>");
 }
+
+#[cfg(feature = "js-esbuild")]
+#[test]
+fn test_style_attr_minification() {
+    eval_with_css_min(b"<div style=\"color: yellow ;\"></div>", b"<div style=color:#ff0></div>");
+    eval_with_css_min(b"<div style=\"margin: 0 auto\"></div>", b"<div style=\"margin:0 auto\"></div>");
+    // Quotes in the minified value are encoded for the quotes used.
+    eval_with_css_min(b"<div style='font-family: \"a b\"'></div>", b"<div style='font-family:\"a b\"'></div>");
+    // Entities are decoded before minifying.
+    eval_with_css_min(b"<div style=\"color: &#x72;ed\"></div>", b"<div style=red></div>");
+    // The attribute is removed if there are no declarations left.
+    eval_with_css_min(b"<div style=\" \" title=a></div>", b"<div title=a></div>");
+    eval_with_css_min(b"<div title=a style=\"/* a */\"></div>", b"<div title=a></div>");
+}
+
+#[cfg(feature = "js-esbuild")]
+#[test]
+fn test_event_handler_attr_minification() {
+    eval_with_js_min(b"<button onclick=\"alert( 1 );\"></button>", b"<button onclick=alert(1)></button>");
+    eval_with_js_min(b"<a href=# onclick=\"return false;\">a</a>", b"<a href=# onclick=return!1>a</a>");
+    eval_with_js_min(b"<img onload=\"if (a) { b(  ); }\">", b"<img onload=a&&b()>");
+    // Values that would form entities are encoded.
+    eval_with_js_min(b"<div onclick=\"let a = 1; b = a &amp;amp c;\"></div>", b"<div onclick=\"let a=1;b=a&amp;amp c\"></div>");
+    // Attributes that are already minimal are kept as is.
+    eval_with_js_min(b"<div onclick=a()></div>", b"<div onclick=a()></div>");
+    // Invalid code is kept as is.
+    eval_with_js_min(b"<div onclick=\"a(  \"></div>", b"<div onclick=\"a(  \"></div>");
+}
+
+#[test]
+fn test_source_map() {
+    let src: &[u8] = b"<div>\n  <p>  Hello &amp; <b>bye</b>  </p>\n</div>";
+    let mut code = src.to_vec();
+    let (len, map) = super::with_source_map(&mut code, &super::Cfg {
+        minify_js: false,
+        minify_css: false,
+        ..super::Cfg::default()
+    }).unwrap();
+    assert_eq!(&code[..len], b"<div><p>Hello & <b>bye</b></div>");
+    // "Hello".
+    for i in 0..5 {
+        assert_eq!(map.source_position(8 + i), 13 + i);
+    };
+    // "&" decoded from "&amp;".
+    assert_eq!(map.source_position(14), 23);
+    // "bye".
+    for i in 0..3 {
+        assert_eq!(map.source_position(19 + i), 28 + i);
+    };
+    let json = map.to_json(src, &code[..len], "index.html");
+    assert!(json.starts_with(r#"{"version":3,"sources":["index.html"],"names":[],"mappings":"#));
+    assert!(json.ends_with(r#""}"#));
+}
+
+#[test]
+fn test_stream() {
+    let mut input: &[u8] = b"<div>  <p>  Hello  </p>  </div>";
+    let mut output = Vec::<u8>::new();
+    let cfg = &super::Cfg {
+        minify_js: false,
+        minify_css: false,
+        ..super::Cfg::default()
+    };
+    super::stream(&mut input, &mut output, cfg).unwrap();
+    assert_eq!(output, b"<div><p>Hello</div>".to_vec());
+
+    let mut input: &[u8] = b"<p></div>";
+    let mut output = Vec::<u8>::new();
+    let err = super::stream(&mut input, &mut output, cfg).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let body = concat!(
+        "  <!-- removed -->  <ul>\n  <li>  one  &amp;&#97;mp; two\n  <li>three &lt;div&gt; <b>bold</b>  <i>italic</i>\n</ul>\n",
+        "<p>Paragraph   with   spaces\n<p>Another &nbsp; one &#x26;amp <span> nested <em> deep </em> </span>\n",
+        "<pre>  keep\n   this   </pre>  <table><tr><td>1<td>2<tr><td>3</table>\n",
+        "<script> let x = 1 ;  </script> <textarea>  text &amp; area </textarea> <!--[if IE]><p>ie</p><![endif]-->\n",
+        "<div title=\"a &quot; b\" class=' c '><SPAN>Mixed</SPAN><x-custom-element-with-a-long-name>x</x-custom-element-with-a-long-name></div>\n",
+    );
+    let src = format!("<!DOCTYPE html>\n<html>\n<head>\n  <title> Streaming &amp; chunks </title>\n</head>\n<body>\n{}</body>\n</html>\n", body.repeat(8));
+    let read_when_written = eval_stream(src.as_bytes(), cfg);
+    // Minified code is written before everything has been read.
+    assert!(read_when_written.len() > 1);
+    assert!(read_when_written[0] < src.len() / 4);
+    eval_stream(format!("<div>{}", body.repeat(4)).as_bytes(), cfg);
+    eval_stream(format!("{}<p>a &am", body.repeat(4)).as_bytes(), cfg);
+    eval_stream(format!("<section>{}<p>Mismatch</div>", body.repeat(4)).as_bytes(), cfg);
+    eval_stream(format!("{}<section>Unclosed{}", body.repeat(4), body.repeat(4)).as_bytes(), cfg);
+}
+
+// Reads `code` in chunks of 1 to 3 bytes, keeping track of how much has been read.
+#[cfg(test)]
+struct ChunkedReader<'c> {
+    code: &'c [u8],
+    read: std::rc::Rc<std::cell::Cell<usize>>,
+}
+
+#[cfg(test)]
+impl std::io::Read for ChunkedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let start = self.read.get();
+        let len = (start % 3 + 1).min(self.code.len() - start).min(buf.len());
+        buf[..len].copy_from_slice(&self.code[start..start + len]);
+        self.read.set(start + len);
+        Ok(len)
+    }
+}
+
+// Keeps track of how much had been read by a `ChunkedReader` whenever something is written.
+#[cfg(test)]
+struct RecordingWriter {
+    output: Vec<u8>,
+    read: std::rc::Rc<std::cell::Cell<usize>>,
+    read_when_written: Vec<usize>,
+}
+
+#[cfg(test)]
+impl std::io::Write for RecordingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.output.extend_from_slice(buf);
+        self.read_when_written.push(self.read.get());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Stream `src` in small chunks and check that the result is the same as minifying it all at once, returning how much
+// had been read whenever minified code was written.
+#[cfg(test)]
+fn eval_stream(src: &[u8], cfg: &super::Cfg) -> Vec<usize> {
+    let read = std::rc::Rc::new(std::cell::Cell::new(0));
+    let mut input = ChunkedReader { code: src, read: read.clone() };
+    let mut output = RecordingWriter { output: Vec::new(), read, read_when_written: Vec::new() };
+    match (super::stream(&mut input, &mut output, cfg), super::copy(src, cfg)) {
+        (Ok(()), Ok(expected)) => {
+            assert_eq!(std::str::from_utf8(&output.output).unwrap(), std::str::from_utf8(&expected).unwrap());
+        }
+        (Err(err), Err(expected)) => {
+            let err = err.into_inner().unwrap().downcast::<super::Error>().unwrap();
+            assert_eq!((&err.error_type, err.position), (&expected.error_type, expected.position));
+            assert!(expected.position > src.len() / 2);
+        }
+        (result, expected) => panic!("streaming gave {:?} but expected {:?}", result, expected.map(|_| ())),
+    };
+    output.read_when_written
+}
+
+#[test]
+fn test_comment_keeping() {
+    let keep_all = super::Cfg {
+        keep_comments: true,
+        ..super::Cfg::default()
+    };
+    _eval(b"<div> <!-- a --> <p>b</p> </div>", b"<div><!-- a --><p>b</div>", &keep_all);
+    _eval(b"<p>a <!-- b --> c</p>", b"<p>a <!-- b --> c", &keep_all);
+    // Closing tag must not be omitted as the comment would otherwise become a child of the previous element.
+    _eval(b"<ul><li>a</li><!-- b --></ul>", b"<ul><li>a</li><!-- b --></ul>", &keep_all);
+    _eval(b"<ul><li>a</li><!-- b --><li>c</li></ul>", b"<ul><li>a</li><!-- b --><li>c</ul>", &keep_all);
+
+    let keep_some = super::Cfg {
+        keep_license_comments: true,
+        keep_ssi_comments: true,
+        keep_comment_prefixes: vec![b" keep".to_vec()],
+        ..super::Cfg::default()
+    };
+    _eval(b"<!--! License --><!-- a --><p>b</p>", b"<!--! License --><p>b", &keep_some);
+    _eval(b"<div> <!--#include virtual=\"/a.html\" --> <!-- b --> </div>", b"<div><!--#include virtual=\"/a.html\" --></div>", &keep_some);
+    _eval(b"<div><!-- keep me --><!-- remove me --></div>", b"<div><!-- keep me --></div>", &keep_some);
+    eval(b"<!--! License --><!--#include virtual=\"/a.html\" --><p>b</p>", b"<p>b");
+}
+
+#[test]
+fn test_conditional_comments() {
+    eval(b"<div> <!--[if IE]> <p>  a  </p> <![endif]--> </div>", b"<div><!--[if IE]><p>a</p><![endif]--></div>");
+    eval(b"<p>a <!--[if lt IE 9]> b <![endif]--> c</p>", b"<p>a <!--[if lt IE 9]> b <![endif]--> c");
+    // Elements can be opened and closed in different conditional comments.
+    eval(
+        b"<!--[if IE]><div class=\"ie\"><![endif]--> <p>a</p> <!--[if IE]></div><![endif]-->",
+        b"<!--[if IE]><div class=ie><![endif]--><p>a</p><!--[if IE]></div><![endif]-->",
+    );
+    // Closing tags must not be omitted before the end of the conditional comment.
+    eval(b"<ul><!--[if IE]><li>a</li><![endif]--></ul>", b"<ul><!--[if IE]><li>a</li><![endif]--></ul>");
+    // Comment end sequence must not be formed.
+    eval(b"<!--[if IE]>--&gt;<![endif]-->", b"<!--[if IE]>--&gt<![endif]-->");
+    // Downlevel-revealed conditional comments.
+    eval(b"<![if !IE]> <p>a</p> <![endif]>", b"<![if !IE]><p>a<![endif]>");
+    eval(b"<!--[if !IE]><!--> <p>a</p> <!--<![endif]-->", b"<!--[if !IE]><!--><p>a</p><!--<![endif]-->");
+    // Malformed conditional comments are regular comments.
+    eval(b"<!--[if IE]> <p>a</p> --><p>b</p>", b"<p>b");
+}
+
+#[cfg(test)]
+fn eval_with_templates(src: &'static [u8], expected: &'static [u8]) -> () {
+    _eval(src, expected, &super::Cfg {
+        template_delimiters: vec![
+            (b"{{".to_vec(), b"}}".to_vec()),
+            (b"{%".to_vec(), b"%}".to_vec()),
+            (b"<%".to_vec(), b"%>".to_vec()),
+            (b"<?php".to_vec(), b"?>".to_vec()),
+        ],
+        ..super::Cfg::default()
+    });
+}
+
+#[test]
+fn test_templates_in_content() {
+    eval_with_templates(b"<p>  Hello,   {{  user.name  }}  !  </p>", b"<p>Hello, {{  user.name  }} !");
+    eval_with_templates(b"<div> {% if a &amp;&amp; b %} <p>a</p> {% endif %} </div>", b"<div>{% if a &amp;&amp; b %} <p>a</p> {% endif %}</div>");
+    eval_with_templates(b"<div><%= a < b %></div>", b"<div><%= a < b %></div>");
+    eval_with_templates(b"<div><?php echo '</div>'; ?></div>", b"<div><?php echo '</div>'; ?></div>");
+    // Closing tags must not be omitted before templates.
+    eval_with_templates(b"<ul><li>a</li>{{ items }}</ul>", b"<ul><li>a</li>{{ items }}</ul>");
+    // Unterminated templates are not templates.
+    eval_with_templates(b"<p>{{   a</p>", b"<p>{{ a");
+    eval_with_templates(b"<p>{{ a {% b }} %} {{</p>", b"<p>{{ a {% b }} %} {{");
+    // Many unterminated templates don't make minification quadratic.
+    let src = b"<p>{{ a</p>".repeat(20000);
+    let cfg = super::Cfg { template_delimiters: vec![(b"{{".to_vec(), b"}}".to_vec())], ..super::Cfg::default() };
+    let start = std::time::Instant::now();
+    assert_eq!(super::copy(&src, &cfg).unwrap(), b"<p>{{ a".repeat(20000));
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn test_templates_in_attributes() {
+    eval_with_templates(b"<div class=\"  {{ a ? 'b' : \"c\" }}  d  \"></div>", b"<div class=\"  {{ a ? 'b' : \"c\" }}  d  \"></div>");
+    eval_with_templates(b"<div title='{{ a }}'  id=b></div>", b"<div title='{{ a }}'id=b></div>");
+    eval_with_templates(b"<div class={{ a b }} id=c></div>", b"<div class={{ a b }} id=c></div>");
+    eval_with_templates(b"<input value={% if a %}\"b\"{% endif %}>", b"<input value={% if a %}\"b\"{% endif %}>");
+    eval_with_templates(b"<input checked=\"{{ a }}\">", b"<input checked=\"{{ a }}\">");
+    eval_with_templates(b"<div {{ attrs }}  ID=a data-{{ Name }}=\"b\"></div>", b"<div {{ attrs }} id=a data-{{ Name }}=b></div>");
+    eval_with_templates(b"<input {% if a %}checked{% endif %}  type=text>", b"<input {% if a %}checked{% endif %}>");
+}
+
+#[test]
+fn test_templates_in_tag_names() {
+    eval_with_templates(b"<H{{ Level }}>a</H{{ Level }}>", b"<H{{ Level }}>a</H{{ Level }}>");
+    eval_with_templates(b"<{{ Tag }} class=\"a\">b</{{ Tag }}>", b"<{{ Tag }} class=a>b</{{ Tag }}>");
+}
+
+#[cfg(test)]
+fn eval_with_escaped_script_data(src: &'static [u8], expected: &'static [u8]) -> () {
+    _eval(src, expected, &super::Cfg {
+        parse_escaped_script_data: true,
+        ..super::Cfg::default()
+    });
+}
+
+#[test]
+fn test_escaped_script_data() {
+    // Examples from `notes/Script data.md`.
+    _eval_error(
+        b"<script type=\"text/html\">\n  <script>\n    exec1 = true;\n  </script>\n  <script>\n    exec2 = true;\n  </script>\n</script>",
+        ErrorType::UnexpectedClosingTag,
+        &super::Cfg {
+            parse_escaped_script_data: true,
+            ..super::Cfg::default()
+        },
+    );
+    eval_with_escaped_script_data(
+        b"<script type=\"text/html\">\n  <script>\n    exec1 = true;\n  </script>\n  <script>\n    exec2 = true;\n  </script>",
+        b"<script type=text/html>\n  <script>\n    exec1 = true;\n  </script><script>\n    exec2 = true;\n  </script>",
+    );
+    eval_with_escaped_script_data(b"<script type=\"text/plain\"><!--\n</script>", b"<script type=text/plain><!--\n</script>");
+    eval_with_escaped_script_data(b"<script type=\"text/plain\">\n  <script>\n</script>", b"<script type=text/plain>\n  <script>\n</script>");
+    eval_with_escaped_script_data(b"<script type=\"text/plain\">\n  <script><!--\n</script>", b"<script type=text/plain>\n  <script><!--\n</script>");
+    eval_with_escaped_script_data(b"<script type=\"text/plain\"><!--\n  <script>-->\n</script>", b"<script type=text/plain><!--\n  <script>-->\n</script>");
+    eval_with_escaped_script_data(b"<script type=\"text/plain\">\n    <!--<script>--><!--\n</script>", b"<script type=text/plain>\n    <!--<script>--><!--\n</script>");
+    eval_with_escaped_script_data(b"<script type=\"text/plain\"><!--\n  <div>\n</script>", b"<script type=text/plain><!--\n  <div>\n</script>");
+    eval_with_escaped_script_data(
+        b"<script type=\"text/plain\"><!--\n  <script>alert();</script>\n</script>",
+        b"<script type=text/plain><!--\n  <script>alert();</script>\n</script>",
+    );
+    eval_with_escaped_script_data(
+        b"<script type=\"text/plain\"><!--\n  <script><script><script><script><script>alert();</script>\n</script>",
+        b"<script type=text/plain><!--\n  <script><script><script><script><script>alert();</script>\n</script>",
+    );
+    eval_with_escaped_script_data(
+        b"<script type=\"text/plain\"><!--\n  <script><script><script>alert();</script>\n  <script></script>\n</script>",
+        b"<script type=text/plain><!--\n  <script><script><script>alert();</script>\n  <script></script>\n</script>",
+    );
+    eval_with_escaped_script_data(
+        b"<script type=\"text/plain\">\n  <!--<script><script><script>--><!--\n</script>",
+        b"<script type=text/plain>\n  <!--<script><script><script>--><!--\n</script>",
+    );
+    eval_with_escaped_script_data(b"<script type=\"text/plain\">\n  <!--<script>-->\n</script>", b"<script type=text/plain>\n  <!--<script>-->\n</script>");
+
+    // `-->` can overlap with `<!--`.
+    eval_with_escaped_script_data(b"<script><!--><script></script>", b"<script><!--><script></script>");
+    // Script tag names must be followed by whitespace, `/`, or `>`.
+    eval_with_escaped_script_data(b"<script><!--<scripts></script>", b"<script><!--<scripts></script>");
+    // Disabled by default.
+    eval_error(b"<script type=\"text/plain\"><!--\n  <script>alert();</script>\n</script>", ErrorType::UnexpectedClosingTag);
+}
+
+#[test]
+fn test_rcdata() {
+    eval(b"<textarea> <b>  a &amp; b </b>\n</textarea>", b"<textarea> <b>  a & b </b>\n</textarea>");
+    eval(b"<title>  a  <b  &amp; </b>  </title>", b"<title> a <b & </b> </title>");
+    eval(b"<TITLE>a</TITLE >", b"<title>a</title>");
+    // Only the matching end tag ends the content.
+    eval(b"<textarea></textareas></title></textarea>", b"<textarea></textareas></title></textarea>");
+    // Entities that could form the end tag must not be decoded.
+    eval(b"<textarea>&lt;/textarea&gt;</textarea>", b"<textarea>&lt;/textarea></textarea>");
+    eval(b"<textarea><&#47;textarea></textarea>", b"<textarea><&#47;textarea></textarea>");
+    eval(b"<title></&#116;itle></title>", b"<title></&#116;itle></title>");
+    eval(b"<title>&lt;b&gt; &#97;</title>", b"<title>&lt;b> a</title>");
+    // SVG `title` elements contain markup.
+    eval(b"<svg><title><tspan>a</tspan></title></svg>", b"<svg><title><tspan>a</tspan></title></svg>");
+}
+
+#[test]
+fn test_rawtext() {
+    eval(b"<xmp>  <b>a &amp; b</b>  </xmp>", b"<xmp>  <b>a &amp; b</b>  </xmp>");
+    eval(b"<noembed><p>a</noembed>", b"<noembed><p>a</noembed>");
+    eval(b"<noframes></noframesx></NOFRAMES>", b"<noframes></noframesx></noframes>");
+    eval(b"<iframe> <p>a</p> </iframe>", b"<iframe> <p>a</p> </iframe>");
+    eval(b"<p>a</p><plaintext>  <p>b</plaintext> &amp;  ", b"<p>a</p><plaintext>  <p>b</plaintext> &amp;  ");
+    // Ancestors of `<plaintext>` are closed at the end as their end tags can't appear.
+    eval(b"<div><plaintext>x</div>y", b"<div><plaintext>x</div>y");
+    eval(b"<div><section><p>a<plaintext>x</div>", b"<div><section><p>a<plaintext>x</div>");
+}
+
+#[cfg(test)]
+fn eval_lenient(src: &'static [u8], expected: &'static [u8], expected_warnings: &[ErrorType]) -> () {
+    let mut code = src.to_vec();
+    let (len, warnings) = super::in_place_lenient(&mut code, &super::Cfg::default()).unwrap();
+    assert_eq!(std::str::from_utf8(&code[..len]).unwrap(), std::str::from_utf8(expected).unwrap());
+    assert_eq!(warnings.into_iter().map(|w| w.error_type).collect::<Vec<_>>(), expected_warnings);
+}
+
+#[test]
+fn test_lenient() {
+    // Stray closing tags are ignored.
+    eval_lenient(b"<div>a</span>b</div></div>", b"<div>ab</div>", &[ErrorType::UnexpectedClosingTag, ErrorType::UnexpectedClosingTag]);
+    eval_lenient(b"</ >a</>", b"a", &[ErrorType::UnexpectedClosingTag, ErrorType::UnexpectedClosingTag]);
+    // Except for `</p>` and `</br>`.
+    eval_lenient(b"<div>a</br>b</P></div>", b"<div>a</br>b</P></div>", &[ErrorType::VoidElementClosingTag("br".to_string()), ErrorType::UnexpectedClosingTag]);
+    // Closing tags for ancestors implicitly close elements.
+    eval_lenient(b"<div><span><b>a</div>", b"<div><span><b>a</div>", &[
+        ErrorType::ClosingTagMismatch { expected: "b".to_string(), got: "div".to_string() },
+        ErrorType::ClosingTagMismatch { expected: "span".to_string(), got: "div".to_string() },
+    ]);
+    // Open elements are implicitly closed at the end.
+    eval_lenient(b"<div><span>a", b"<div><span>a", &[ErrorType::UnclosedElement("span".to_string()), ErrorType::UnclosedElement("div".to_string())]);
+    eval_lenient(b"<textarea>a", b"<textarea>a", &[ErrorType::UnclosedElement("textarea".to_string())]);
+    eval_lenient(b"<div>a<!-- b", b"<div>a", &[ErrorType::UnexpectedEnd, ErrorType::UnclosedElement("div".to_string())]);
+    // Closing tags can have attributes.
+    eval_lenient(b"<div>a</div class=b>", b"<div>a</div>", &[ErrorType::NotFound { expected: "closing tag end", construct: "closing tag", start: 6 }]);
+    // No warnings for valid code.
+    eval_lenient(b"<ul><li>a</ul>", b"<ul><li>a</ul>", &[]);
+}
+
+#[test]
+fn test_diagnose() {
+    use super::{diagnose, Severity};
+    let diagnostics = diagnose("<p a=1 b A=2>\n\u{1F600}é&#x110000;<br></br></p>\n<div></span>\n<i".as_bytes(), &super::Cfg::default());
+    let summary = diagnostics.iter().map(|d| (&d.error_type, d.severity, d.line, d.column, d.position, d.utf16_position)).collect::<Vec<_>>();
+    assert_eq!(summary, vec![
+        (&ErrorType::DuplicateAttribute("a".to_string()), Severity::Warning, 1, 10, 9, 9),
+        (&ErrorType::InvalidEntity, Severity::Warning, 2, 3, 20, 17),
+        (&ErrorType::VoidElementClosingTag("br".to_string()), Severity::Warning, 2, 17, 34, 31),
+        (&ErrorType::UnexpectedClosingTag, Severity::Warning, 3, 6, 49, 46),
+        (&ErrorType::UnexpectedEnd, Severity::Error, 4, 3, 59, 56),
+    ]);
+    // Diagnostics are sorted even though outer elements are found to be unclosed last.
+    let diagnostics = diagnose(b"<div><p>a&#99999999;", &super::Cfg::default());
+    assert_eq!(diagnostics.iter().map(|d| (&d.error_type, d.position)).collect::<Vec<_>>(), vec![
+        (&ErrorType::UnclosedElement("div".to_string()), 0),
+        (&ErrorType::InvalidEntity, 9),
+    ]);
+    assert!(diagnose(b"<p a=1>&amp;</p>", &super::Cfg::default()).is_empty());
+    // Attribute names can contain any non-ASCII bytes, even if they aren't valid UTF-8.
+    let diagnostics = diagnose(b"<a \xff=1 \xff=2>x</a>", &super::Cfg::default());
+    assert_eq!(diagnostics.iter().map(|d| (&d.error_type, d.position)).collect::<Vec<_>>(), vec![
+        (&ErrorType::DuplicateAttribute("\u{FFFD}".to_string()), 7),
+    ]);
+}
+
+#[test]
+fn test_error_display_and_codes() {
+    use super::{copy, with_friendly_error};
+    let err = copy(b"<p>a</p><!doctype html", &super::Cfg::default()).unwrap_err();
+    assert_eq!(err.error_type, ErrorType::NotFound { expected: "bang close", construct: "bang", start: 8 });
+    assert_eq!(err.error_type.code(), "not-found");
+    assert_eq!(err.to_string(), "Expected bang close for bang starting at position 8. (at position 10)");
+    let err = copy(b"<p>\n<!-- a", &super::Cfg::default()).unwrap_err();
+    assert_eq!(err.error_type, ErrorType::NotFound { expected: "comment end", construct: "comment", start: 4 });
+    let err = copy(b"<div></span>", &super::Cfg::default()).unwrap_err();
+    assert_eq!(err.error_type.code(), "closing-tag-mismatch");
+    let source: &dyn std::error::Error = &err;
+    assert_eq!(source.source().unwrap().to_string(), err.error_type.message());
+    let err = with_friendly_error(&mut b"<p></div>".to_vec(), &super::Cfg::default()).unwrap_err();
+    assert_eq!(err.to_string(), "Unexpected closing tag. (at position 3)\n1|<p></div>\n>|   ^ \n");
+}
+
+#[cfg(test)]
+struct WhitespaceMinifier {
+    threaded: bool,
+}
+
+#[cfg(test)]
+impl super::ContentMinifier for WhitespaceMinifier {
+    fn minify(&self, _kind: super::CodeKind, code: &[u8]) -> super::MinifyResult {
+        if code.starts_with(b"invalid") {
+            return Err(super::MinifyError::Failed(vec!["Invalid code.".to_string()]));
+        };
+        if code.starts_with(b"unsupported") {
+            return Err(super::MinifyError::Unsupported);
+        };
+        Ok(code.iter().filter(|c| !c.is_ascii_whitespace()).copied().collect())
+    }
+
+    fn minify_async(&self, kind: super::CodeKind, code: Vec<u8>, done: Box<dyn FnOnce(super::MinifyResult) + Send>) -> () {
+        if self.threaded {
+            let result = self.minify(kind, &code);
+            std::thread::spawn(move || done(result));
+        } else {
+            done(self.minify(kind, &code));
+        };
+    }
+}
+
+#[cfg(test)]
+fn eval_with_minifier(src: &'static [u8], expected: &'static [u8]) -> () {
+    for threaded in vec![false, true] {
+        _eval(src, expected, &super::Cfg {
+            minify_js: true,
+            minify_css: true,
+            minifier: Some(std::sync::Arc::new(WhitespaceMinifier { threaded })),
+            ..super::Cfg::default()
+        });
+    };
+}
+
+#[test]
+fn test_content_minifier() {
+    eval_with_minifier(b"<script> a = 1 ; </script>", b"<script>a=1;</script>");
+    eval_with_minifier(b"<style> a { color : red } </style><script> b </script>", b"<style>a{color:red}</style><script>b</script>");
+    eval_with_minifier(b"<div style=\" color : red \" onclick=\" f ( ) \"></div>", b"<div style=\"color:red\"onclick=\"f()\"></div>");
+    eval_with_minifier(b"<div style=\"  \" title=a></div>", b"<div title=a></div>");
+    eval_with_minifier(b"<div onclick=\"  \" title=a></div>", b"<div onclick=\"\"title=a></div>");
+    // Code that can't be minified is kept as is.
+    eval_with_minifier(b"<script>invalid  code</script>", b"<script>invalid  code</script>");
+    eval_with_minifier(b"<script type=text/plain> a </script>", b"<script type=text/plain> a </script>");
+    // Minified code is escaped.
+    eval_with_minifier(b"<script>a = '</ script>'</script>", b"<script>a='<\\/script>'</script>");
+    eval_with_minifier(b"<div style='a: \"&#39;\"'></div>", b"<div style=a:\"'\"></div>");
+    _eval(b"<script> a = 1 ; </script>", b"<script> a = 1 ; </script>", &super::Cfg {
+        minify_js: false,
+        minifier: Some(std::sync::Arc::new(WhitespaceMinifier { threaded: false })),
+        ..super::Cfg::default()
+    });
+}
+
+#[cfg(feature = "css-builtin")]
+#[cfg(test)]
+fn eval_with_builtin_css(src: &'static [u8], expected: &'static [u8]) -> () {
+    _eval(src, expected, &super::Cfg {
+        minify_css: true,
+        minifier: Some(std::sync::Arc::new(super::BuiltinCssMinifier)),
+        ..super::Cfg::default()
+    });
+}
+
+#[cfg(feature = "css-builtin")]
+#[test]
+fn test_builtin_css_minifier() {
+    eval_with_builtin_css(b"<style>\n  a > b ,  c ~ d {\n    color : #FFFFFF ;\n    margin: 0px 0.50em -0.0rem 010px ;\n  }\n</style>", b"<style>a>b,c~d{color:#fff;margin:0 .5em -0 10px}</style>");
+    // Comments and empty declarations are removed.
+    eval_with_builtin_css(b"<style>/* a */ a { ; color: red;; /* b */ } </style>", b"<style>a{color:red}</style>");
+    // Whitespace in selectors, at-rule preludes, and `calc` is kept where it matters.
+    eval_with_builtin_css(b"<style>a :hover, a  b { width: calc(1px + 2%) }</style>", b"<style>a :hover,a b{width:calc(1px + 2%)}</style>");
+    eval_with_builtin_css(b"<style>@media screen and (max-width : 100px) { a:hover { top: 0 } }</style>", b"<style>@media screen and (max-width:100px){a:hover{top:0}}</style>");
+    // Units that aren't always optional are kept.
+    eval_with_builtin_css(b"<style>a { transition: opacity 0s; flex: 1 1 0%; }</style>", b"<style>a{transition:opacity 0s;flex:1 1 0%}</style>");
+    // Zero lengths in math functions need their units.
+    eval_with_builtin_css(b"<style>a{width:calc(0px + 1em)}</style>", b"<style>a{width:calc(0px + 1em)}</style>");
+    eval_with_builtin_css(b"<style>a { height: max(0px, 1vh); margin: 0px }</style>", b"<style>a{height:max(0px,1vh);margin:0}</style>");
+    eval_with_builtin_css(b"<div style=\"width: calc(100% - 0px)\"></div>", b"<div style=\"width:calc(100% - 0px)\"></div>");
+    // Selectors, identifiers, strings, URLs, and custom properties are kept as is.
+    eval_with_builtin_css(b"<style>#aabbcc, .a0px { grid-area: col-01; content: \"  #aabbcc  0px \"; background: url( a  b.png ); --x:  0px  1px ; }</style>", b"<style>#aabbcc,.a0px{grid-area:col-01;content:\"  #aabbcc  0px \";background:url(a  b.png);--x:0px  1px}</style>");
+    eval_with_builtin_css(b"<style>@import url(a.css) ;  a { color: #aabbccdd }</style>", b"<style>@import url(a.css);a{color:#abcd}</style>");
+    // `</style` is escaped.
+    eval_with_builtin_css(b"<style>a::after { content: \"<\\/style>\" }</style>", b"<style>a::after{content:\"<\\/style>\"}</style>");
+    // Invalid CSS is kept as is.
+    eval_with_builtin_css(b"<style>a { content: \"a</style>", b"<style>a { content: \"a</style>");
+    // Style attributes.
+    eval_with_builtin_css(b"<div style=\"color : #ff0000 ; margin: 0px ; \"></div>", b"<div style=\"color:#f00;margin:0\"></div>");
+    eval_with_builtin_css(b"<div style=\"/* a */\" title=a></div>", b"<div title=a></div>");
+}
+
+#[cfg(test)]
+fn eval_with_json_min(src: &'static [u8], expected: &'static [u8]) -> () {
+    _eval(src, expected, &super::Cfg {
+        minify_json: true,
+        ..super::Cfg::default()
+    });
+}
+
+#[test]
+fn test_json_script_minification() {
+    eval_with_json_min(b"<script type=\"application/ld+json\">\n  {\n    \"@type\" : \"Person\",\n    \"name\": \"A  B\",\n    \"age\": [ -1.50e+3, 0, true, false, null, {}, [ ] ]\n  }\n</script>", b"<script type=application/ld+json>{\"@type\":\"Person\",\"name\":\"A  B\",\"age\":[-1.50e+3,0,true,false,null,{},[]]}</script>");
+    eval_with_json_min(b"<script type=importmap> { \"imports\": { \"a\": \"./a.js\" } } </script>", b"<script type=importmap>{\"imports\":{\"a\":\"./a.js\"}}</script>");
+    eval_with_json_min(b"<script type=speculationrules> { \"prerender\": [ { \"urls\": [ \"/a\" ] } ] } </script>", b"<script type=speculationrules>{\"prerender\":[{\"urls\":[\"/a\"]}]}</script>");
+    eval_with_json_min(b"<script type=application/json> \"a \\\" \\u00e9 \\/ b\" </script>", b"<script type=application/json>\"a \\\" \\u00e9 \\/ b\"</script>");
+    eval_with_json_min(b"<script type=application/json> [ \"<\\/script>\" ] </script>", b"<script type=application/json>[\"<\\/script>\"]</script>");
+    // Invalid JSON is kept as is.
+    eval_with_json_min(b"<script type=application/json> { a: 1 } </script>", b"<script type=application/json> { a: 1 } </script>");
+    eval_with_json_min(b"<script type=application/json> [1, 2,] </script>", b"<script type=application/json> [1, 2,] </script>");
+    eval_with_json_min(b"<script type=application/json> [01] </script>", b"<script type=application/json> [01] </script>");
+    eval_with_json_min(b"<script type=application/json> [\"a\tb\"] </script>", b"<script type=application/json> [\"a\tb\"] </script>");
+    eval_with_json_min(b"<script type=application/json> {} {} </script>", b"<script type=application/json> {} {} </script>");
+    eval_with_json_min(b"<script type=application/json> [[1] </script>", b"<script type=application/json> [[1] </script>");
+    eval_with_json_min(b"<script type=application/json> </script>", b"<script type=application/json> </script>");
+    // Other script types aren't minified.
+    eval_with_json_min(b"<script type=text/template> { \"a\": 1 } </script>", b"<script type=text/template> { \"a\": 1 } </script>");
+    // JSON isn't minified unless enabled.
+    eval(b"<script type=importmap> { \"imports\": {} } </script>", b"<script type=importmap> { \"imports\": {} } </script>");
+}
+
+#[cfg(test)]
+struct KindMinifier;
+
+#[cfg(test)]
+impl super::ContentMinifier for KindMinifier {
+    fn minify(&self, kind: super::CodeKind, _code: &[u8]) -> super::MinifyResult {
+        Ok(format!("{:?}", kind).into_bytes())
+    }
+}
+
+#[test]
+fn test_script_types() {
+    let cfg = super::Cfg {
+        minify_js: true,
+        js_mime_types: vec![b"text/x-custom".to_vec()],
+        minifier: Some(std::sync::Arc::new(KindMinifier)),
+        ..super::Cfg::default()
+    };
+    _eval(b"<script type=text/javascript>a = 1</script>", b"<script>Js</script>", &cfg);
+    _eval(b"<script type=module>import a from 'a'</script>", b"<script type=module>JsModule</script>", &cfg);
+    _eval(b"<script type=text/x-custom>a = 1</script>", b"<script type=text/x-custom>Js</script>", &cfg);
+    _eval(b"<script type=text/x-other>a = 1</script>", b"<script type=text/x-other>a = 1</script>", &cfg);
+    _eval(b"<script type=text/x-custom>a = 1</script>", b"<script type=text/x-custom>a = 1</script>", &super::Cfg {
+        minify_js: true,
+        minifier: Some(std::sync::Arc::new(KindMinifier)),
+        ..super::Cfg::default()
+    });
+}
+
+#[test]
+fn test_minify_failure_policy() {
+    use super::{Cfg, CodeKind, Error, MinifyFailurePolicy, in_place, in_place_lenient, in_place_with_warnings};
+    let src = b"<script>invalid  code</script><div style=\"invalid: x\" onclick=\"unsupported()\"></div>";
+    let cfg = |on_minify_failure| Cfg {
+        minify_js: true,
+        minify_css: true,
+        minifier: Some(std::sync::Arc::new(WhitespaceMinifier { threaded: true })),
+        on_minify_failure,
+        ..Cfg::default()
+    };
+    let (_, warnings) = in_place_lenient(&mut src.to_vec(), &cfg(MinifyFailurePolicy::Warn)).unwrap();
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].position, 8);
+    assert_eq!(warnings[0].error_type, ErrorType::MinifyFailed {
+        kind: CodeKind::Js,
+        end: 21,
+        messages: vec!["Invalid code.".to_string()],
+    });
+    assert_eq!(warnings[0].error_type.to_string(), "Could not minify JS code ending at position 21: Invalid code.");
+    assert_eq!(warnings[1].position, 35);
+    assert_eq!(warnings[1].error_type.code(), "minify-failed");
+    match &warnings[1].error_type {
+        ErrorType::MinifyFailed { kind, end, .. } => assert_eq!((*kind, *end), (CodeKind::CssDeclarations, 53)),
+        _ => panic!(),
+    };
+    assert_eq!(in_place_lenient(&mut src.to_vec(), &cfg(MinifyFailurePolicy::Ignore)).unwrap().1.len(), 0);
+    assert!(in_place(&mut src.to_vec(), &cfg(MinifyFailurePolicy::Warn)).is_ok());
+    // Warnings are also returned without lenient mode.
+    let (_, strict_warnings) = in_place_with_warnings(&mut src.to_vec(), &cfg(MinifyFailurePolicy::Warn)).unwrap();
+    assert_eq!(strict_warnings.iter().map(|w| (&w.error_type, w.position)).collect::<Vec<_>>(), warnings.iter().map(|w| (&w.error_type, w.position)).collect::<Vec<_>>());
+    let Error { error_type, position } = in_place(&mut src.to_vec(), &cfg(MinifyFailurePolicy::Fail)).unwrap_err();
+    assert_eq!((error_type.code(), position), ("minify-failed", 8));
+    // Code that can't be minified because it's not supported is never reported.
+    assert!(in_place(&mut b"<script>unsupported</script>".to_vec(), &cfg(MinifyFailurePolicy::Fail)).is_ok());
+}
+
+#[cfg(feature = "js-esbuild")]
+#[test]
+fn test_esbuild_options() {
+    use super::{CodeKind, ContentMinifier, EsbuildMinifier, EsbuildOptions, EsbuildTarget};
+    let code = b"(function () { let count = 1; console.log(count); })();";
+    let default = EsbuildMinifier::new().minify(CodeKind::Js, code).unwrap();
+    assert!(!String::from_utf8(default).unwrap().contains("count"));
+    let keep_names = EsbuildMinifier::with_options(EsbuildOptions {
+        keep_names: true,
+        ..EsbuildOptions::default()
+    });
+    assert!(String::from_utf8(keep_names.minify(CodeKind::Js, code).unwrap()).unwrap().contains("count"));
+    // `let` can't be lowered to ES5.
+    let es5 = EsbuildMinifier::with_options(EsbuildOptions {
+        target: EsbuildTarget::ES5,
+        ..EsbuildOptions::default()
+    });
+    assert!(es5.minify(CodeKind::Js, code).is_err());
+}
+
+#[cfg(test)]
+struct CountingMinifier {
+    calls: std::sync::atomic::AtomicUsize,
+}
+
+#[cfg(test)]
+impl super::ContentMinifier for CountingMinifier {
+    fn minify(&self, kind: super::CodeKind, code: &[u8]) -> super::MinifyResult {
+        self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        WhitespaceMinifier { threaded: false }.minify(kind, code)
+    }
+}
+
+#[test]
+fn test_in_place_batch() {
+    use std::sync::Arc;
+    use std::sync::atomic::Ordering;
+    let minifier = Arc::new(CountingMinifier { calls: Default::default() });
+    let cfg = super::Cfg {
+        minify_js: true,
+        minifier: Some(minifier.clone()),
+        ..super::Cfg::default()
+    };
+    let mut codes = (0..50)
+        .map(|i| format!("<p>  {}  </p><script> a ( ) </script><div onclick=\" b ( {} ) \"></div>", i, i % 2).into_bytes())
+        .collect::<Vec<_>>();
+    codes.push(b"<div></span>".to_vec());
+    for threads in vec![0, 1, 4] {
+        minifier.calls.store(0, Ordering::SeqCst);
+        let mut minified = codes.clone();
+        let results = super::in_place_batch(&mut minified, &cfg, threads);
+        assert_eq!(results.len(), codes.len());
+        let len = *results[7].as_ref().unwrap();
+        assert_eq!(&minified[7][..len], &b"<p>7</p><script>a()</script><div onclick=\"b(1)\"></div>"[..]);
+        assert_eq!(results[50].as_ref().unwrap_err().error_type.code(), "closing-tag-mismatch");
+        // The script is minified once, and the event handler once for each distinct value.
+        assert_eq!(minifier.calls.load(Ordering::SeqCst), 3);
+    };
+    // A provided cache is used and kept between calls.
+    let cache = Arc::new(super::MinifyCache::new());
+    assert!(cache.is_empty());
+    let cfg = super::Cfg {
+        cache: Some(cache.clone()),
+        ..cfg
+    };
+    minifier.calls.store(0, Ordering::SeqCst);
+    super::in_place_batch(&mut codes.clone(), &cfg, 0);
+    super::in_place(&mut codes[0].clone(), &cfg).unwrap();
+    assert_eq!(cache.len(), 3);
+    assert!(!cache.is_empty());
+    assert_eq!(minifier.calls.load(Ordering::SeqCst), 3);
+    // Caches with a maximum number of entries remove the oldest.
+    let cache = Arc::new(super::MinifyCache::new().with_max_entries(2));
+    let cfg = super::Cfg {
+        cache: Some(cache.clone()),
+        ..cfg
+    };
+    minifier.calls.store(0, Ordering::SeqCst);
+    super::in_place(&mut codes[0].clone(), &cfg).unwrap();
+    super::in_place(&mut codes[1].clone(), &cfg).unwrap();
+    assert_eq!(cache.len(), 2);
+    assert_eq!(minifier.calls.load(Ordering::SeqCst), 3);
+    // The script was minified first, so it was removed when the second event handler was minified.
+    super::in_place(&mut codes[1].clone(), &cfg).unwrap();
+    assert_eq!(cache.len(), 2);
+    assert_eq!(minifier.calls.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn test_minify_cache_dir() {
+    use std::sync::Arc;
+    use std::sync::atomic::Ordering;
+    let dir = std::env::temp_dir().join(format!("minify-html-test-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let minifier = Arc::new(CountingMinifier { calls: Default::default() });
+    let cfg = |cache| super::Cfg {
+        minify_js: true,
+        minify_css: true,
+        minifier: Some(minifier.clone()),
+        cache: Some(Arc::new(cache)),
+        ..super::Cfg::default()
+    };
+    let src = b"<script> a ( ) </script><style> a { } </style><div style=\" a { } \"></div>";
+    let expected = b"<script>a()</script><style>a{}</style><div style=\"a{}\"></div>";
+    // The same code is cached separately for each kind.
+    let first = super::copy(src, &cfg(super::MinifyCache::with_dir(&dir).unwrap())).unwrap();
+    assert_eq!(first, expected.to_vec());
+    assert_eq!(minifier.calls.load(Ordering::SeqCst), 3);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+    // A new cache using the same directory reuses the minified code.
+    let second = super::copy(src, &cfg(super::MinifyCache::with_dir(&dir).unwrap())).unwrap();
+    assert_eq!(second, expected.to_vec());
+    assert_eq!(minifier.calls.load(Ordering::SeqCst), 3);
+    // Files for different code with the same name are not used.
+    for entry in std::fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let mut contents = std::fs::read(&path).unwrap();
+        contents[8] ^= 1;
+        std::fs::write(&path, contents).unwrap();
+    };
+    let third = super::copy(src, &cfg(super::MinifyCache::with_dir(&dir).unwrap())).unwrap();
+    assert_eq!(third, expected.to_vec());
+    assert_eq!(minifier.calls.load(Ordering::SeqCst), 6);
+    // Caches without a directory don't.
+    super::copy(src, &cfg(super::MinifyCache::new())).unwrap();
+    assert_eq!(minifier.calls.load(Ordering::SeqCst), 9);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(test)]
+fn tokens(src: &'static [u8]) -> Vec<String> {
+    tokens_with_cfg(src, &super::Cfg::default())
+}
+
+#[cfg(test)]
+fn tokens_with_cfg(src: &[u8], cfg: &super::Cfg) -> Vec<String> {
+    use super::Token;
+    super::Tokenizer::with_cfg(src, cfg).map(|t| match t.unwrap() {
+        Token::StartTag(tag) => format!("<{}{}>", String::from_utf8_lossy(tag.name.bytes), if tag.namespace == super::Namespace::Svg { " svg" } else { "" }),
+        Token::EndTag(tag) => format!("</{}{}>", String::from_utf8_lossy(tag.name.bytes), if tag.implied { " implied" } else { "" }),
+        Token::Text(text) => format!("text {}", String::from_utf8_lossy(text.bytes)),
+        Token::Comment { content, .. } => format!("comment {}", String::from_utf8_lossy(content.bytes)),
+        Token::Bang(bang) => format!("bang {}", String::from_utf8_lossy(bang.bytes)),
+        Token::Instruction(instruction) => format!("instruction {}", String::from_utf8_lossy(instruction.bytes)),
+        Token::RawText(text) => format!("raw {}", String::from_utf8_lossy(text.bytes)),
+    }).collect()
+}
+
+#[test]
+fn test_tokenizer() {
+    use super::{ErrorType, Token, Tokenizer};
+    assert_eq!(tokens(b"<!DOCTYPE html><?xml?><!-- a --><ul><li>1<li>2</ul>"), vec![
+        "bang <!DOCTYPE html>", "instruction <?xml?>", "comment  a ", "<ul>", "<li>", "text 1", "</li implied>", "<li>", "text 2", "</li implied>", "</ul>",
+    ]);
+    assert_eq!(tokens(b"<p>a<div>b</div>a < b<br><P>c"), vec![
+        "<p>", "text a", "</p implied>", "<div>", "text b", "</div>", "text a < b", "<br>", "<P>", "text c", "</P implied>",
+    ]);
+    assert_eq!(tokens(b"<svg><title>a</title><path/></svg><title><b></TITLE>"), vec![
+        "<svg svg>", "<title svg>", "text a", "</title>", "<path svg>", "</svg>", "<title>", "raw <b>", "</TITLE>",
+    ]);
+    assert_eq!(tokens(b"<div><span>a</div></span>"), vec![
+        "<div>", "<span>", "text a", "</span implied>", "</div>", "</span>",
+    ]);
+    assert_eq!(tokens(b"<script>a</scripts></script ><script></script>"), vec![
+        "<script>", "raw a</scripts>", "</script>", "<script>", "</script>",
+    ]);
+    // Names are case insensitive, including long ones that aren't handled specially.
+    assert_eq!(tokens(b"<UL><Li>a<LI>b</ul><X-VERY-LONG-ELEMENT-NAME>c</x-very-long-element-name>"), vec![
+        "<UL>", "<Li>", "text a", "</Li implied>", "<LI>", "text b", "</LI implied>", "</ul>", "<X-VERY-LONG-ELEMENT-NAME>", "text c", "</x-very-long-element-name>",
+    ]);
+
+    let src = b"<a href=\"/\" download class = x>";
+    let tag = match Tokenizer::new(src).next().unwrap().unwrap() {
+        Token::StartTag(tag) => tag,
+        _ => panic!("expected start tag"),
+    };
+    assert_eq!(tag.span.range, 0..src.len());
+    assert_eq!(tag.attributes.len(), 3);
+    assert_eq!(tag.attributes[0].span.range, 3..11);
+    assert_eq!(tag.attributes[0].value.as_ref().unwrap().range, 9..10);
+    assert_eq!(tag.attributes[1].name.bytes, b"download");
+    assert_eq!(tag.attributes[1].value, None);
+    assert_eq!(tag.attributes[2].name.bytes, b"class");
+    assert_eq!(tag.attributes[2].value.as_ref().unwrap().bytes, b"x");
+
+    let mut tokenizer = Tokenizer::new(b"a<!-- b");
+    assert!(tokenizer.next().unwrap().is_ok());
+    match tokenizer.next().unwrap() {
+        Err(err) => {
+            assert_eq!(err.position, 1);
+            assert!(matches!(err.error_type, ErrorType::NotFound { expected: "comment end", .. }));
+        }
+        Ok(_) => panic!("expected error"),
+    };
+    assert!(tokenizer.next().is_none());
+}
+
+#[cfg(test)]
+fn template_cfg() -> super::Cfg {
+    super::Cfg {
+        template_delimiters: vec![(b"{{".to_vec(), b"}}".to_vec()), (b"<%".to_vec(), b"%>".to_vec())],
+        ..super::Cfg::default()
+    }
+}
+
+#[test]
+fn test_tokenizer_templates() {
+    use super::{Token, Tokenizer};
+    let cfg = template_cfg();
+    assert_eq!(tokens_with_cfg(b"<p>{{ a <b> }}<%= c %></p><h{{ n }}>d</h{{ n }}><{{ e }}></{{ e }}>", &cfg), vec![
+        "<p>", "text {{ a <b> }}<%= c %>", "</p>", "<h{{ n }}>", "text d", "</h{{ n }}>", "<{{ e }}>", "</{{ e }}>",
+    ]);
+    // Without templates, `<b>` is a tag.
+    assert_eq!(tokens(b"<p>{{ a <b> }}</p>"), vec!["<p>", "text {{ a ", "<b>", "text  }}", "</b implied>", "</p>"]);
+    // Unterminated templates are not templates.
+    assert_eq!(tokens_with_cfg(b"<p>{{ a <b>", &cfg), vec!["<p>", "text {{ a ", "<b>", "</b implied>", "</p implied>"]);
+
+    let src = b"<a {{ attrs }} data-{{ n }}=1 title=\"{{ \"a\" }}\" href={{ url | a b }}>";
+    let tag = match Tokenizer::with_cfg(src, &cfg).next().unwrap().unwrap() {
+        Token::StartTag(tag) => tag,
+        _ => panic!("expected start tag"),
+    };
+    assert_eq!(tag.span.range, 0..src.len());
+    let attributes = tag.attributes.iter().map(|a| (a.name.bytes, a.value.as_ref().map(|v| v.bytes))).collect::<Vec<_>>();
+    assert_eq!(attributes, vec![
+        (&b"{{ attrs }}"[..], None),
+        (b"data-{{ n }}", Some(&b"1"[..])),
+        (b"title", Some(b"{{ \"a\" }}")),
+        (b"href", Some(b"{{ url | a b }}")),
+    ]);
+
+    // Many unterminated templates don't make tokenizing quadratic.
+    let src = b"<p>{{ a</p>".repeat(20000);
+    let start = std::time::Instant::now();
+    assert_eq!(Tokenizer::with_cfg(&src, &cfg).count(), 20000 * 3);
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
+
+// Elements found by the tokenizer, with lowercase names, ignoring whether end tags are implied.
+#[cfg(test)]
+fn tokenized_elements(src: &[u8], cfg: &super::Cfg) -> Vec<String> {
+    use super::Token;
+    super::Tokenizer::with_cfg(src, cfg).filter_map(|t| match t.unwrap() {
+        Token::StartTag(tag) => Some(format!("<{}>", String::from_utf8_lossy(tag.name.bytes).to_lowercase())),
+        Token::EndTag(tag) => Some(format!("</{}>", String::from_utf8_lossy(tag.name.bytes).to_lowercase())),
+        _ => None,
+    }).collect()
+}
+
+// The tokenizer finds the same elements in the source as in the minifier's output, so both parse it the same way.
+#[cfg(test)]
+fn assert_tokenizer_matches_minifier(src: &[u8], cfg: &super::Cfg) -> () {
+    let mut min = src.to_vec();
+    let min_len = super::in_place(&mut min, cfg)
+        .unwrap_or_else(|err| panic!("{:?} in {}", err.error_type, String::from_utf8_lossy(src)));
+    assert_eq!(tokenized_elements(&min[..min_len], cfg), tokenized_elements(src, cfg), "{}", String::from_utf8_lossy(src));
+}
+
+#[test]
+fn test_tokenizer_matches_minifier() {
+    let srcs: &[&[u8]] = &[
+        b"<!DOCTYPE html><html><head><title>a <b></title><style>p{}</style></head><body><p>a<p>b<ul><li>c<li>d</ul>",
+        b"<table><tr><td>a<td>b<tr><th>c</table><dl><dt>a<dd>b<dt>c<dd>d</dl>",
+        b"<select><optgroup><option>a<option>b<optgroup><option>c</select><ruby>a<rt>b<rp>c</ruby>",
+        b"<div><script>a = '</div>'; b = '<p>';</script><textarea><p></textarea><xmp><p></xmp></div>",
+        b"<svg><title><b>a</b></title><path/></svg><p><svg><style>b</style></svg>c",
+        b"<p>a <!-- <p> --> <?b?> <!c> <br> <img src=d>",
+        b"<plaintext><p>a</p>",
+    ];
+    for src in srcs {
+        assert_tokenizer_matches_minifier(src, &super::Cfg::default());
+        assert_tokenizer_matches_minifier(src, &template_cfg());
+    };
+    let templated_srcs: &[&[u8]] = &[
+        b"<div>{{ a <b> }}<%= c < d %></div><p title=\"{{ \"e\" }}\">f</p>",
+        b"<h{{ n }}>a</h{{ n }}><{{ tag }} {{ attrs }}>b</{{ tag }}>",
+        b"<ul><li>a</li>{{ items }}<li>b</ul>",
+    ];
+    for src in templated_srcs {
+        assert_tokenizer_matches_minifier(src, &template_cfg());
+    };
+}
+
+#[cfg(test)]
+struct TestHook;
+
+#[cfg(test)]
+impl super::Hook for TestHook {
+    fn element(&self, name: &[u8]) -> super::HookAction {
+        match name {
+            b"template" => super::HookAction::Drop,
+            b"x-icon" => super::HookAction::Replace(b"<i></i>".to_vec()),
+            b"x-long" => super::HookAction::Replace(b"<span>longer</span>".to_vec()),
+            _ => super::HookAction::Keep,
+        }
+    }
+
+    fn attribute(&self, element: &[u8], name: &[u8], value: Option<&[u8]>) -> super::HookAction {
+        match (element, name, value) {
+            (_, name, _) if name.starts_with(b"data-test-") => super::HookAction::Drop,
+            (b"a", b"href", Some(value)) if value.starts_with(b"http://example.com/") => {
+                super::HookAction::Replace(value[b"http://example.com".len()..].to_vec())
+            }
+            (_, _, Some(b"empty")) => super::HookAction::Replace(Vec::new()),
+            (_, b"alt", _) => super::HookAction::Replace(b"a longer value".to_vec()),
+            (_, b"style", _) => super::HookAction::Replace(b"color : red".to_vec()),
+            _ => super::HookAction::Keep,
+        }
+    }
+}
+
+#[cfg(test)]
+fn eval_with_hook(src: &'static [u8], expected: &'static [u8]) -> () {
+    _eval(src, expected, &super::Cfg {
+        hook: Some(std::sync::Arc::new(TestHook)),
+        ..super::Cfg::default()
+    });
+}
+
+#[test]
+fn test_hook() {
+    eval_with_hook(b"<a data-test-id=\"1\" href=\"http://example.com/a b\" class=x>a</a>", b"<a href=\"/a b\"class=x>a</a>");
+    eval_with_hook(b"<a href=http://example.com/&amp;b data-test-id>a</a>", b"<a href=/&b>a</a>");
+    eval_with_hook(b"<div data-a=empty id=a></div>", b"<div data-a id=a></div>");
+    // Dropped elements are removed entirely, including omitted end tags.
+    eval_with_hook(b"<div>a<template><p>b<script>c</script></template>d</div>", b"<div>ad</div>");
+    eval_with_hook(b"<ul><li>a<template></template></li><li>b</ul>", b"<ul><li>a<li>b</ul>");
+    eval_with_hook(b"<p>a <x-icon name=star></x-icon> b", b"<p>a <i></i> b");
+    eval_with_hook(b"<div>a <template>z</template> b</div>", b"<div>a b</div>");
+    eval_with_hook(b"<p>a <template></template>\n<template></template>", b"<p>a");
+    eval_with_hook(b"<p>a <a data-test-id=1>b</a>", b"<p>a <a>b</a>");
+    // Replaced values are still minified.
+    _eval(b"<div style=\"color: blue\"></div>", b"<div style=\"color:red\"></div>", &super::Cfg {
+        minify_css: true,
+        minifier: Some(std::sync::Arc::new(WhitespaceMinifier { threaded: false })),
+        hook: Some(std::sync::Arc::new(TestHook)),
+        ..super::Cfg::default()
+    });
+    // Replacements can't be longer than the source code when minifying a slice, but can be when minifying a Vec.
+    let cfg = super::Cfg { hook: Some(std::sync::Arc::new(TestHook)), ..super::Cfg::default() };
+    _eval_error(b"<img alt=a>", ErrorType::ReplacementTooLong, &cfg);
+    _eval_error(b"<x-long></x-long>", ErrorType::ReplacementTooLong, &cfg);
+    _eval_copy(b"<img alt=a>", b"<img alt=\"a longer value\">", &cfg);
+    _eval_copy(b"<p><x-long></x-long><x-long></x-long>a", b"<p><span>longer</span><span>longer</span>a", &cfg);
+    eval_with_hook(b"<p><x-long>                 </x-long></p>", b"<p><span>longer</span>");
+}
+
+#[cfg(test)]
+fn eval_with_removal(src: &'static [u8], expected: &'static [u8]) -> () {
+    _eval(src, expected, &super::Cfg {
+        remove_attributes: vec![b"data-testid".to_vec(), b"ng-reflect-*".to_vec()],
+        remove_elements: vec!["template".parse().unwrap(), "[hidden]".parse().unwrap(), "div[data-role=\"test\"]".parse().unwrap()],
+        ..super::Cfg::default()
+    });
+}
+
+#[test]
+fn test_removal() {
+    use super::Selector;
+    eval_with_removal(b"<a DATA-TESTID=1 ng-reflect-model=a ng-model=b data-testids=c>a</a>", b"<a ng-model=b data-testids=c>a</a>");
+    eval_with_removal(b"<div>a<TEMPLATE><p>b</template>c</div>", b"<div>ac</div>");
+    eval_with_removal(b"<ul><li>a<li hidden>b<li>c</ul>", b"<ul><li>a<li>c</ul>");
+    eval_with_removal(b"<div data-role=test><p>a</div><div data-role=&#116;est></div><div data-role=tests></div>", b"<div data-role=tests></div>");
+    eval_with_removal(b"<p>a</p><span hidden>b</span>c", b"<p>a</p>c");
+    eval_with_removal(b"<div><p>a</p><span hidden>b</span></div>", b"<div><p>a</div>");
+    // Whitespace around a removed element is collapsed as if the element wasn't there.
+    _eval(b"<div>a <span>z</span> b</div>", b"<div>a b</div>", &super::Cfg {
+        remove_elements: vec!["span".parse().unwrap()],
+        ..super::Cfg::default()
+    });
+    eval_with_removal(b"<div>a <span hidden>b</span></div>", b"<div>a</div>");
+    eval_with_removal(b"<div>a <template></template> <template></template> b</div>", b"<div>a b</div>");
+    // Classes and IDs are matched like attributes.
+    _eval(b"<div class=\"x ad\">a</div><div class=adx>b</div><p id=banner>c</p><p id=Banner>d</p><span class=\"ad&#9;y\">e</span>", b"<div class=adx>b</div><p id=Banner>d", &super::Cfg {
+        remove_elements: vec![".ad".parse().unwrap(), "p#banner".parse().unwrap()],
+        ..super::Cfg::default()
+    });
+
+    assert!(Selector::parse("*").is_some());
+    assert!(Selector::parse("*[a='b]']").is_some());
+    assert!(Selector::parse("a[b][c=d]").is_some());
+    assert!(Selector::parse("").is_none());
+    assert!(Selector::parse("a b").is_none());
+    assert!(Selector::parse("a[b").is_none());
+    assert!(Selector::parse("a[=b]").is_none());
+    assert!(Selector::parse("a[b=\"c]").is_none());
+    assert!(Selector::parse("a.b#c.d[e]").is_some());
+    assert!(Selector::parse(".").is_none());
+    assert!(Selector::parse("a.b:hover").is_none());
+    assert!(Selector::parse("#a > b").is_none());
+    assert!(Selector::parse("a, b").is_none());
+    assert!(Selector::parse("[a~=b]").is_none());
+    assert_eq!("a b".parse::<Selector>().unwrap_err(), "Invalid selector \"a b\".");
+}
+
+#[cfg(test)]
+fn eval_with_whitespace_groups(src: &'static [u8], expected: &'static [u8]) -> () {
+    use super::WhitespaceGroup;
+    let mut whitespace_groups = std::collections::HashMap::new();
+    whitespace_groups.insert(b"x-card".to_vec(), WhitespaceGroup::Layout);
+    whitespace_groups.insert(b"text".to_vec(), WhitespaceGroup::Content);
+    whitespace_groups.insert(b"x-code".to_vec(), WhitespaceGroup::WhitespaceSensitive);
+    whitespace_groups.insert(b"div".to_vec(), WhitespaceGroup::Formatting);
+    _eval(src, expected, &super::Cfg {
+        whitespace_groups,
+        ..super::Cfg::default()
+    });
+}
+
+#[test]
+fn test_whitespace_groups() {
+    eval(b"<x-card>\n  <p>a</p>\n  <p>b</p>\n</x-card>", b"<x-card> <p>a</p> <p>b</p> </x-card>");
+    eval_with_whitespace_groups(b"<x-card>\n  <p>a</p>\n  <p>b</p>\n</x-card>", b"<x-card><p>a<p>b</x-card>");
+    eval_with_whitespace_groups(b"<svg><text>  a  </text></svg>", b"<svg><text>a</text></svg>");
+    eval_with_whitespace_groups(b"<x-code>  a  </x-code>", b"<x-code>  a  </x-code>");
+    // Descendants of whitespace sensitive elements are whitespace sensitive too, like those of `pre`.
+    eval_with_whitespace_groups(b"<x-code>  a  <b>  b  <i>  c  </i></b>  </x-code>", b"<x-code>  a  <b>  b  <i>  c  </i></b>  </x-code>");
+    eval_with_whitespace_groups(b"<section><x-code> <p>  a  </p> </x-code>  </section>", b"<section><x-code> <p>  a  </p> </x-code></section>");
+    // Built-in groups can be overridden.
+    eval_with_whitespace_groups(b"<div>  <span>a</span>  </div>", b"<div> <span>a</span> </div>");
+}
+
+#[cfg(test)]
+fn eval_with_inferred_whitespace_sensitivity(src: &'static [u8], expected: &'static [u8]) -> () {
+    _eval(src, expected, &super::Cfg {
+        infer_whitespace_sensitivity: true,
+        ..super::Cfg::default()
+    });
+}
+
+#[test]
+fn test_inferred_whitespace_sensitivity() {
+    eval(b"<div style=\"white-space: pre\">  a  <p>  b  </p></div>", b"<div style=\"white-space: pre\">a <p>b</div>");
+    eval_with_inferred_whitespace_sensitivity(b"<div style=\"white-space: pre\">  a  <p>  b  </p></div>", b"<div style=\"white-space: pre\">  a  <p>  b  </div>");
+    eval_with_inferred_whitespace_sensitivity(b"<div style=\"color: red; WHITE-SPACE : pre-wrap !important\">  a  </div>", b"<div style=\"color: red; WHITE-SPACE : pre-wrap !important\">  a  </div>");
+    eval_with_inferred_whitespace_sensitivity(b"<div style=white-space:pre-line>  a  </div>", b"<div style=white-space:pre-line>  a  </div>");
+    eval_with_inferred_whitespace_sensitivity(b"<div style=white-space:normal>  a  </div>", b"<div style=white-space:normal>a</div>");
+    eval_with_inferred_whitespace_sensitivity(b"<div style=\"--white-space: pre\">  a  </div>", b"<div style=\"--white-space: pre\">a</div>");
+    eval_with_inferred_whitespace_sensitivity(b"<div contenteditable>  a  </div>", b"<div contenteditable>  a  </div>");
+    eval_with_inferred_whitespace_sensitivity(b"<div contenteditable=plaintext-only>  a  </div>", b"<div contenteditable=plaintext-only>  a  </div>");
+    eval_with_inferred_whitespace_sensitivity(b"<div contenteditable=false>  a  </div>", b"<div contenteditable=false>a</div>");
+    // Whitespace after the element is minified as usual.
+    eval_with_inferred_whitespace_sensitivity(b"<div><span contenteditable> a </span>  </div>", b"<div><span contenteditable> a </span></div>");
+}
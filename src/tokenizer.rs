@@ -0,0 +1,484 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use memchr::{memchr, memmem};
+
+use crate::cfg::Cfg;
+use crate::err::{Error, ErrorType};
+use crate::gen::codepoints::{ATTR_NAME_CHAR, DOUBLE_QUOTE, Lookup, NOT_UNQUOTED_ATTR_VAL_CHAR, SINGLE_QUOTE, TAG_NAME_CHAR, WHITESPACE};
+use crate::spec::tag::ns::Namespace;
+use crate::spec::tag::omission::can_omit_name_as_before;
+use crate::spec::tag::text::{get_text_content, TextContent};
+use crate::spec::tag::void::VOID_TAGS;
+
+/// A part of the source code.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Span<'a> {
+    /// Where the part is in the source code.
+    pub range: Range<usize>,
+    /// The part of the source code, as is.
+    pub bytes: &'a [u8],
+}
+
+/// An attribute in a start tag, like `name="value"`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Attribute<'a> {
+    /// The entire attribute.
+    pub span: Span<'a>,
+    pub name: Span<'a>,
+    /// The value without any quotes, with entities not decoded, or None if there's no `=`.
+    pub value: Option<Span<'a>>,
+}
+
+/// A start tag, like `<div id="a">`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StartTag<'a> {
+    /// The entire tag, from `<` to `>`.
+    pub span: Span<'a>,
+    /// The tag name, in its original case.
+    pub name: Span<'a>,
+    /// The namespace of the element.
+    pub namespace: Namespace,
+    pub attributes: Vec<Attribute<'a>>,
+    /// Whether the tag ends with `/>`. Self-closing and void elements don't have end tags.
+    pub self_closing: bool,
+}
+
+/// An end tag, like `</div>`, or where an end tag is implied.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EndTag<'a> {
+    /// The entire tag, from `</` to `>`, or an empty span where the end tag is implied.
+    pub span: Span<'a>,
+    /// The tag name in its original case, from the start tag if the end tag is implied.
+    pub name: Span<'a>,
+    /// Whether the end tag was omitted, as allowed by the
+    /// [specification](https://html.spec.whatwg.org/multipage/syntax.html#syntax-tag-omission) (e.g. `</li>` before
+    /// another `<li>`), or because an ancestor was closed or the source code ended.
+    pub implied: bool,
+}
+
+/// A part of an HTML document found by a `Tokenizer`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Token<'a> {
+    StartTag(StartTag<'a>),
+    EndTag(EndTag<'a>),
+    /// Text content, as is, with entities not decoded.
+    Text(Span<'a>),
+    /// A comment, including `<!--` and `-->`, and its content between them.
+    Comment { span: Span<'a>, content: Span<'a> },
+    /// A tag starting with `<!`, like `<!DOCTYPE html>`.
+    Bang(Span<'a>),
+    /// A processing instruction, like `<?xml version="1.0"?>`.
+    Instruction(Span<'a>),
+    /// The content of an element whose content isn't HTML, like `<script>`, `<style>`, or
+    /// `<textarea>`, as is.
+    RawText(Span<'a>),
+}
+
+// An element that has been started but not ended.
+struct OpenElement<'a> {
+    name: Span<'a>,
+    // The namespace of the element's children.
+    child_ns: Namespace,
+}
+
+// Lowercase `name` into `buf`. Names of elements that are handled specially, like `blockquote`, are short enough to fit,
+// so longer names are returned as is, as they won't match any of them.
+fn lowercase<'b>(name: &'b [u8], buf: &'b mut [u8; 16]) -> &'b [u8] {
+    match buf.get_mut(..name.len()) {
+        Some(lowercase) => {
+            lowercase.copy_from_slice(name);
+            lowercase.make_ascii_lowercase();
+            lowercase
+        }
+        None => name,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RawTextEnd {
+    // Content ends at the end tag for the last opened element.
+    EndTag,
+    // Content ends at the end of the source code.
+    End,
+}
+
+/// Iterates through the tags, text, and other parts of an HTML document without modifying or
+/// copying it. End tags are implied where they're omitted using the same rules that the minifier
+/// uses, so every start tag of a non-void element has a matching end tag.
+///
+/// Templates are recognised using `Cfg::template_delimiters` if created using
+/// `Tokenizer::with_cfg`, in the same places as when minifying: text containing templates is
+/// returned as is, and templates can be in tag names, attribute names, and attribute values.
+///
+/// An error is returned if something can't be parsed, such as an unterminated comment, after
+/// which iteration ends.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html::{Token, Tokenizer};
+///
+/// let tokens = Tokenizer::new(b"<ul><li>a<li>b</ul>").collect::<Result<Vec<_>, _>>().unwrap();
+/// let end_tags = tokens
+///     .iter()
+///     .filter_map(|t| match t {
+///         Token::EndTag(tag) => Some((tag.name.bytes, tag.implied)),
+///         _ => None,
+///     })
+///     .collect::<Vec<_>>();
+/// assert_eq!(end_tags, vec![(&b"li"[..], true), (b"li", true), (b"ul", false)]);
+/// ```
+pub struct Tokenizer<'a> {
+    code: &'a [u8],
+    template_delimiters: Vec<(Vec<u8>, Vec<u8>)>,
+    // For each template delimiter, where its closing delimiter is known not to be found at or after, so that many
+    // unterminated templates don't make tokenizing quadratic.
+    template_close_missing_from: RefCell<Vec<usize>>,
+    pos: usize,
+    open: Vec<OpenElement<'a>>,
+    // Content of the last opened element that isn't HTML and must be read next.
+    raw_text: Option<RawTextEnd>,
+    // Tokens found but not yet returned.
+    queue: VecDeque<Token<'a>>,
+    done: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(code: &'a [u8]) -> Tokenizer<'a> {
+        Tokenizer::with_template_delimiters(code, Vec::new())
+    }
+
+    /// Create a tokenizer that parses `code` using the same options as minifying it with `cfg`.
+    pub fn with_cfg(code: &'a [u8], cfg: &Cfg) -> Tokenizer<'a> {
+        Tokenizer::with_template_delimiters(code, cfg.template_delimiters.clone())
+    }
+
+    fn with_template_delimiters(code: &'a [u8], template_delimiters: Vec<(Vec<u8>, Vec<u8>)>) -> Tokenizer<'a> {
+        Tokenizer {
+            code,
+            template_close_missing_from: RefCell::new(vec![usize::MAX; template_delimiters.len()]),
+            template_delimiters,
+            pos: 0,
+            open: Vec::new(),
+            raw_text: None,
+            queue: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span<'a> {
+        Span { range: start..end, bytes: &self.code[start..end] }
+    }
+
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.code.get(self.pos + offset).copied()
+    }
+
+    fn skip_while(&mut self, lookup: &Lookup) -> () {
+        while self.peek(0).filter(|c| lookup[*c]).is_some() {
+            self.pos += 1;
+        };
+    }
+
+    // Length of the template at `pos`, including its delimiters, like `template_len` when minifying.
+    fn template_len(&self, pos: usize) -> Option<usize> {
+        let rest = self.code.get(pos..)?;
+        let (i, (open, close)) = self.template_delimiters.iter().enumerate().find(|(_, (open, _))| rest.starts_with(open))?;
+        let close_start = pos + open.len();
+        let mut missing_from = self.template_close_missing_from.borrow_mut();
+        if close_start >= missing_from[i] {
+            return None;
+        };
+        match memmem::find(&self.code[close_start..], close) {
+            Some(p) => Some(open.len() + p + close.len()),
+            None => {
+                missing_from[i] = close_start;
+                None
+            }
+        }
+    }
+
+    // Skip a name made of characters in `lookup` and any templates, like `match_name` when minifying.
+    fn skip_name(&mut self, lookup: &Lookup) -> () {
+        loop {
+            match self.template_len(self.pos) {
+                Some(len) => self.pos += len,
+                None if self.peek(0).filter(|c| lookup[*c]).is_some() => self.pos += 1,
+                None => break,
+            };
+        };
+    }
+
+    // Skip an attribute value until a character in `end` that isn't in a template.
+    fn skip_attr_value(&mut self, end: &Lookup) -> () {
+        loop {
+            match self.template_len(self.pos) {
+                Some(len) => self.pos += len,
+                None if self.peek(0).filter(|c| !end[*c]).is_some() => self.pos += 1,
+                None => break,
+            };
+        };
+    }
+
+    fn not_found(expected: &'static str, construct: &'static str, start: usize) -> Error {
+        Error { error_type: ErrorType::NotFound { expected, construct, start }, position: start }
+    }
+
+    // Whether the `<` at `pos` starts something other than text.
+    fn is_markup_start(&self, pos: usize) -> bool {
+        match self.code.get(pos + 1) {
+            Some(b'/') => self.code.get(pos + 2).filter(|c| TAG_NAME_CHAR[**c]).is_some() || self.template_len(pos + 2).is_some(),
+            Some(b'!') | Some(b'?') => true,
+            Some(c) => TAG_NAME_CHAR[*c] || self.template_len(pos + 1).is_some(),
+            None => false,
+        }
+    }
+
+    // Position where the text at `start` ends, which is where something that isn't text starts.
+    fn text_end(&self, start: usize) -> usize {
+        let mut pos = start;
+        if self.template_delimiters.is_empty() {
+            loop {
+                match memchr(b'<', &self.code[pos..]) {
+                    Some(p) if self.is_markup_start(pos + p) => return pos + p,
+                    Some(p) => pos += p + 1,
+                    None => return self.code.len(),
+                };
+            };
+        };
+        // Templates are treated like text, even if they contain `<`.
+        while pos < self.code.len() {
+            match self.template_len(pos) {
+                Some(len) => pos += len,
+                None if self.code[pos] == b'<' && self.is_markup_start(pos) => break,
+                None => pos += 1,
+            };
+        };
+        pos
+    }
+
+    // Position after `seq` if found at or after `from`.
+    fn find_after(&self, from: usize, seq: &[u8]) -> Option<usize> {
+        self.code[from.min(self.code.len())..].windows(seq.len()).position(|w| w == seq).map(|p| from + p + seq.len())
+    }
+
+    fn imply_end_tag(&mut self, element: OpenElement<'a>) -> () {
+        let span = self.span(self.pos, self.pos);
+        self.queue.push_back(Token::EndTag(EndTag { span, name: element.name, implied: true }));
+    }
+
+    fn read_raw_text(&mut self, end: RawTextEnd) -> () {
+        let start = self.pos;
+        self.pos = match end {
+            RawTextEnd::End => self.code.len(),
+            RawTextEnd::EndTag => {
+                let name = self.open.last().unwrap().name.bytes;
+                let mut pos = start;
+                loop {
+                    match memchr(b'<', &self.code[pos..]) {
+                        None => break self.code.len(),
+                        Some(p) => pos += p,
+                    };
+                    let name_end = pos + 2 + name.len();
+                    if self.code.get(pos + 1) == Some(&b'/')
+                        && self.code.get(pos + 2..name_end).filter(|n| n.eq_ignore_ascii_case(name)).is_some()
+                        && self.code.get(name_end).map_or(true, |c| WHITESPACE[*c] || *c == b'/' || *c == b'>') {
+                        break pos;
+                    };
+                    pos += 1;
+                }
+            }
+        };
+        if self.pos > start {
+            let span = self.span(start, self.pos);
+            self.queue.push_back(Token::RawText(span));
+        };
+    }
+
+    fn read_start_tag(&mut self) -> Result<(), Error> {
+        let start = self.pos;
+        self.pos += 1;
+        let name_start = self.pos;
+        self.skip_name(TAG_NAME_CHAR);
+        let name = self.span(name_start, self.pos);
+        let mut lowercase_buf = [0u8; 16];
+        let lowercase_name = lowercase(name.bytes, &mut lowercase_buf);
+        let mut attributes = Vec::new();
+        let mut self_closing = false;
+        loop {
+            self.skip_while(WHITESPACE);
+            match (self.peek(0), self.peek(1)) {
+                (None, _) => return Err(Tokenizer::not_found("tag end", "tag", start)),
+                (Some(b'>'), _) => {
+                    self.pos += 1;
+                    break;
+                }
+                (Some(b'/'), Some(b'>')) => {
+                    self.pos += 2;
+                    self_closing = true;
+                    break;
+                }
+                _ => attributes.push(self.read_attribute()?),
+            };
+        };
+
+        // Close elements whose end tags are implied by this start tag.
+        let mut last_buf = [0u8; 16];
+        while self.open.last().filter(|e| can_omit_name_as_before(Some(lowercase(e.name.bytes, &mut last_buf)), lowercase_name)).is_some() {
+            let element = self.open.pop().unwrap();
+            self.imply_end_tag(element);
+        };
+
+        let ns = self.open.last().map_or(Namespace::Html, |e| e.child_ns);
+        let namespace = if lowercase_name == b"svg" { Namespace::Svg } else { ns };
+        let span = self.span(start, self.pos);
+        self.queue.push_back(Token::StartTag(StartTag { span, name: name.clone(), namespace, attributes, self_closing }));
+        if self_closing || VOID_TAGS.contains(lowercase_name) {
+            return Ok(());
+        };
+        self.raw_text = get_text_content(ns, lowercase_name).map(|content| match content {
+            TextContent::PlainText => RawTextEnd::End,
+            _ => RawTextEnd::EndTag,
+        });
+        self.open.push(OpenElement { name, child_ns: namespace });
+        Ok(())
+    }
+
+    fn read_attribute(&mut self) -> Result<Attribute<'a>, Error> {
+        let start = self.pos;
+        self.skip_name(ATTR_NAME_CHAR);
+        if self.pos == start {
+            return Err(Tokenizer::not_found("attribute name", "attribute", start));
+        };
+        let name = self.span(start, self.pos);
+        let name_end = self.pos;
+        self.skip_while(WHITESPACE);
+        if self.peek(0) != Some(b'=') {
+            // Don't include whitespace after a name without a value.
+            self.pos = name_end;
+            return Ok(Attribute { span: name.clone(), name, value: None });
+        };
+        self.pos += 1;
+        self.skip_while(WHITESPACE);
+        let quote = match self.peek(0) {
+            Some(b'"') => Some(DOUBLE_QUOTE),
+            Some(b'\'') => Some(SINGLE_QUOTE),
+            _ => None,
+        };
+        let value = match quote {
+            Some(quote) => {
+                let quote_start = self.pos;
+                self.pos += 1;
+                self.skip_attr_value(quote);
+                if self.peek(0).is_none() {
+                    return Err(Tokenizer::not_found("attribute value closing quote", "attribute value", quote_start));
+                };
+                self.pos += 1;
+                self.span(quote_start + 1, self.pos - 1)
+            }
+            None => {
+                let value_start = self.pos;
+                self.skip_attr_value(NOT_UNQUOTED_ATTR_VAL_CHAR);
+                self.span(value_start, self.pos)
+            }
+        };
+        Ok(Attribute { span: self.span(start, self.pos), name, value: Some(value) })
+    }
+
+    fn read_end_tag(&mut self) -> Result<(), Error> {
+        let start = self.pos;
+        self.pos += 2;
+        let name_start = self.pos;
+        self.skip_name(TAG_NAME_CHAR);
+        let name = self.span(name_start, self.pos);
+        // Anything else in the end tag, like attributes, is ignored.
+        self.pos = memchr(b'>', &self.code[self.pos..])
+            .map(|p| self.pos + p + 1)
+            .ok_or_else(|| Tokenizer::not_found("closing tag end", "closing tag", start))?;
+        // An end tag for an ancestor implicitly closes all elements inside it. End tags that don't match any open
+        // element are returned as is.
+        if let Some(i) = self.open.iter().rposition(|e| name.bytes.eq_ignore_ascii_case(e.name.bytes)) {
+            let end = self.pos;
+            self.pos = start;
+            while self.open.len() > i + 1 {
+                let element = self.open.pop().unwrap();
+                self.imply_end_tag(element);
+            };
+            self.open.pop();
+            self.pos = end;
+        };
+        let span = self.span(start, self.pos);
+        self.queue.push_back(Token::EndTag(EndTag { span, name, implied: false }));
+        Ok(())
+    }
+
+    fn read_next(&mut self) -> Result<(), Error> {
+        if let Some(end) = self.raw_text.take() {
+            self.read_raw_text(end);
+            return Ok(());
+        };
+        let start = self.pos;
+        match (self.peek(0), self.peek(1)) {
+            // Templates are checked first, as delimiters could look like something else.
+            (Some(_), _) if self.template_len(start).is_some() => self.read_text(start),
+            (None, _) => {
+                // Close all open elements.
+                while let Some(element) = self.open.pop() {
+                    self.imply_end_tag(element);
+                };
+                self.done = true;
+            }
+            (Some(b'<'), Some(b'/')) if self.is_markup_start(start) => self.read_end_tag()?,
+            (Some(b'<'), Some(b'!')) if self.code[start..].starts_with(b"<!--") => {
+                self.pos = self.find_after(start + 4, b"-->").ok_or_else(|| Tokenizer::not_found("comment end", "comment", start))?;
+                let span = self.span(start, self.pos);
+                let content = self.span(start + 4, self.pos - 3);
+                self.queue.push_back(Token::Comment { span, content });
+            }
+            (Some(b'<'), Some(b'!')) => {
+                self.pos = memchr(b'>', &self.code[start..])
+                    .map(|p| start + p + 1)
+                    .ok_or_else(|| Tokenizer::not_found("bang close", "bang", start))?;
+                let span = self.span(start, self.pos);
+                self.queue.push_back(Token::Bang(span));
+            }
+            (Some(b'<'), Some(b'?')) => {
+                self.pos = self.find_after(start + 2, b"?>").ok_or_else(|| Tokenizer::not_found("instruction end", "instruction", start))?;
+                let span = self.span(start, self.pos);
+                self.queue.push_back(Token::Instruction(span));
+            }
+            (Some(b'<'), _) if self.is_markup_start(start) => self.read_start_tag()?,
+            _ => self.read_text(start),
+        };
+        Ok(())
+    }
+
+    fn read_text(&mut self, start: usize) -> () {
+        // Text continues until something that isn't text.
+        self.pos = self.text_end(start);
+        let span = self.span(start, self.pos);
+        self.queue.push_back(Token::Text(span));
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(token) = self.queue.pop_front() {
+                return Some(Ok(token));
+            };
+            if self.done {
+                return None;
+            };
+            if let Err(err) = self.read_next() {
+                self.done = true;
+                return Some(Err(err));
+            };
+        };
+    }
+}
+
@@ -0,0 +1,69 @@
+use crate::cfg::Cfg;
+use crate::minifier::CodeKind;
+use crate::proc::entity::decode_entities;
+use crate::proc::{MinifiedSection, MinifiedSectionResult, Processor};
+use crate::proc::range::ProcessorRange;
+use crate::unit::attr::value::encode_attr_value;
+
+// A processed attribute with a value to minify.
+#[derive(Clone, Copy)]
+pub struct AttrCode {
+    // The entire written attribute, including any space before it.
+    pub attr: ProcessorRange,
+    // The written value, including any quotes.
+    pub value: ProcessorRange,
+    // Where the attribute starts and ends in the source code.
+    pub source_start: usize,
+    pub source_end: usize,
+    pub quoted: bool,
+}
+
+// Minify an attribute value using the configured minifier. Once minified, the attribute is replaced with one using the
+// minified value (if shorter), or removed if the minified value is empty and the code is CSS declarations, as an empty
+// `style` attribute does nothing. `followed_by_attr` is whether another attribute is written after this one, which
+// must be known before calling this.
+pub fn minify_attr(proc: &mut Processor, cfg: &Cfg, AttrCode { attr, value, source_start, source_end, quoted }: AttrCode, kind: CodeKind, followed_by_attr: bool) -> () {
+    let minifier = match cfg.content_minifier() {
+        Some(minifier) => minifier,
+        None => return,
+    };
+    let raw = &proc[value];
+    let raw = if quoted { &raw[1..raw.len() - 1] } else { raw };
+    let code = decode_entities(raw, true);
+    let written = &proc[attr];
+    let leading_space = written[0] == b' ';
+    let name_start = leading_space as usize;
+    let name = written[name_start..name_start + written[name_start..].iter().position(|c| *c == b'=').unwrap()].to_vec();
+    let send = proc.new_minified_section();
+    let policy = cfg.on_minify_failure;
+    let remove_if_empty = kind == CodeKind::CssDeclarations;
+    cfg.minify_async(minifier, kind, code, Box::new(move |result| {
+        let section = match result {
+            Ok(min_value) => {
+                let mut escaped = Vec::<u8>::new();
+                if min_value.is_empty() && remove_if_empty {
+                    // A quoted value could be directly followed by another attribute, so keep any space before it.
+                    if leading_space && quoted && followed_by_attr {
+                        escaped.push(b' ');
+                    };
+                } else {
+                    if leading_space {
+                        escaped.push(b' ');
+                    };
+                    escaped.extend_from_slice(&name);
+                    escaped.push(b'=');
+                    escaped.extend(encode_attr_value(&min_value, quoted));
+                };
+                Some(MinifiedSection {
+                    src: attr,
+                    result: MinifiedSectionResult::Escaped(escaped),
+                })
+            }
+            Err(err) => MinifiedSection::failed(attr, source_start, source_end, kind, err, policy),
+        };
+        if let Some(section) = section {
+            // The receiver is only dropped after all senders have been dropped.
+            send.send(section).unwrap();
+        };
+    }));
+}
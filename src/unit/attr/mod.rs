@@ -1,15 +1,18 @@
+use crate::cfg::Cfg;
 use crate::err::ProcessingResult;
 use crate::proc::checkpoint::WriteCheckpoint;
 use crate::proc::MatchAction::*;
 use crate::proc::MatchMode::*;
 use crate::proc::Processor;
 use crate::proc::range::ProcessorRange;
-use crate::unit::attr::value::{DelimiterType, process_attr_value, ProcessedAttrValue, skip_attr_value};
+use crate::unit::attr::value::{DelimiterType, process_attr_value, process_templated_attr_value, ProcessedAttrValue, skip_attr_value};
+use crate::unit::template::match_name;
 use crate::gen::attrs::ATTRS;
 use crate::spec::tag::ns::Namespace;
 use crate::gen::codepoints::{ATTR_NAME_CHAR, WHITESPACE};
 
-mod value;
+pub mod minify;
+pub mod value;
 
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub enum AttrType {
@@ -22,13 +25,19 @@ pub struct ProcessedAttr {
     pub name: ProcessorRange,
     pub typ: AttrType,
     pub value: Option<ProcessorRange>,
+    // Whether the name or value contains a template.
+    pub templated: bool,
 }
 
-pub fn process_attr(proc: &mut Processor, ns: Namespace, element: ProcessorRange) -> ProcessingResult<ProcessedAttr> {
+pub fn process_attr(proc: &mut Processor, cfg: &Cfg, ns: Namespace, element: ProcessorRange) -> ProcessingResult<ProcessedAttr> {
     // It's possible to expect attribute name but not be called at an attribute, e.g. due to whitespace between name and
     // value, which causes name to be considered boolean attribute and `=` to be start of new (invalid) attribute name.
-    let name = proc.m(WhileInLookup(ATTR_NAME_CHAR), Keep).require("attribute name")?;
-    proc.make_lowercase(name);
+    let start = proc.read_len();
+    let (name, name_has_template) = match_name(proc, cfg, ATTR_NAME_CHAR, Keep);
+    name.require("attribute name", "attribute", start)?;
+    if !name_has_template {
+        proc.make_lowercase(name);
+    };
     let attr_cfg = ATTRS.get(ns, &proc[element], &proc[name]);
     let is_boolean = attr_cfg.filter(|attr| attr.boolean).is_some();
     let after_name = WriteCheckpoint::new(proc);
@@ -37,11 +46,15 @@ pub fn process_attr(proc: &mut Processor, ns: Namespace, element: ProcessorRange
     proc.m(WhileInLookup(WHITESPACE), Discard);
     let has_value = proc.m(IsChar(b'='), Keep).nonempty();
 
+    let mut templated = name_has_template;
     let (typ, value) = if !has_value {
         (AttrType::NoValue, None)
     } else {
         proc.m(WhileInLookup(WHITESPACE), Discard);
-        if is_boolean {
+        if let Some(ProcessedAttrValue { delimiter, value }) = process_templated_attr_value(proc, cfg)? {
+            templated = true;
+            (if delimiter == DelimiterType::Unquoted { AttrType::Unquoted } else { AttrType::Quoted }, value)
+        } else if is_boolean {
             skip_attr_value(proc)?;
             // Discard `=`.
             debug_assert_eq!(after_name.written_count(proc), 1);
@@ -61,5 +74,5 @@ pub fn process_attr(proc: &mut Processor, ns: Namespace, element: ProcessorRange
         }
     };
 
-    Ok(ProcessedAttr { name, typ, value })
+    Ok(ProcessedAttr { name, typ, value, templated })
 }
@@ -2,14 +2,17 @@ use std::collections::HashMap;
 
 use lazy_static::lazy_static;
 
-use crate::err::ProcessingResult;
+use crate::cfg::Cfg;
+use crate::err::{ErrorType, ProcessingResult};
 use crate::gen::codepoints::{ATTR_QUOTE, DIGIT, DOUBLE_QUOTE, NOT_UNQUOTED_ATTR_VAL_CHAR, SINGLE_QUOTE, WHITESPACE};
 use crate::proc::checkpoint::WriteCheckpoint;
 use crate::proc::entity::maybe_normalise_entity;
+use crate::proc::entity::is_entity_start;
 use crate::proc::MatchAction::*;
 use crate::proc::MatchMode::*;
 use crate::proc::Processor;
 use crate::proc::range::ProcessorRange;
+use crate::unit::template::template_len;
 
 // See comment in `process_attr_value` for full description of why these intentionally do not have semicolons.
 lazy_static! {
@@ -146,6 +149,7 @@ impl Metrics {
 }
 
 pub fn skip_attr_value(proc: &mut Processor) -> ProcessingResult<()> {
+    let value_start = proc.read_len();
     let src_delimiter = proc.m(IsInLookup(ATTR_QUOTE), Discard).first(proc);
     let delim_pred = match src_delimiter {
         Some(b'"') => DOUBLE_QUOTE,
@@ -155,7 +159,7 @@ pub fn skip_attr_value(proc: &mut Processor) -> ProcessingResult<()> {
     };
     proc.m(WhileNotInLookup(delim_pred), Discard);
     if let Some(c) = src_delimiter {
-        proc.m(IsChar(c), Discard).require("attribute value closing quote")?;
+        proc.m(IsChar(c), Discard).require("attribute value closing quote", "attribute value", value_start)?;
     };
     Ok(())
 }
@@ -165,6 +169,58 @@ pub struct ProcessedAttrValue {
     pub value: Option<ProcessorRange>,
 }
 
+// If the attribute value contains a template, copy it as is, including any quotes, as the rendered value is unknown.
+// Returns None and does nothing if the value has no templates.
+pub fn process_templated_attr_value(proc: &mut Processor, cfg: &Cfg) -> ProcessingResult<Option<ProcessedAttrValue>> {
+    if cfg.template_delimiters.is_empty() {
+        return Ok(None);
+    };
+    let src_delimiter = proc.peek(0).filter(|c| ATTR_QUOTE[*c]);
+    let delim_lookup = match src_delimiter {
+        Some(b'"') => DOUBLE_QUOTE,
+        Some(b'\'') => SINGLE_QUOTE,
+        None => NOT_UNQUOTED_ATTR_VAL_CHAR,
+        _ => unreachable!(),
+    };
+    let mut len = src_delimiter.is_some() as usize;
+    let mut has_template = false;
+    loop {
+        // Quotes and whitespace inside templates don't end the value.
+        if let Some(template_len) = template_len(proc, cfg, len) {
+            has_template = true;
+            len += template_len;
+            continue;
+        };
+        match proc.peek(len) {
+            Some(c) if !delim_lookup[c] => len += 1,
+            _ => break,
+        };
+    };
+    if !has_template {
+        return Ok(None);
+    };
+    if let Some(c) = src_delimiter {
+        if proc.peek(len) != Some(c) {
+            return Err(ErrorType::NotFound {
+                expected: "attribute value closing quote",
+                construct: "attribute value",
+                start: proc.read_len(),
+            });
+        };
+        len += 1;
+    };
+    let start = WriteCheckpoint::new(proc);
+    proc.accept_amount_expect(len);
+    Ok(Some(ProcessedAttrValue {
+        delimiter: match src_delimiter {
+            Some(b'"') => DelimiterType::Double,
+            Some(b'\'') => DelimiterType::Single,
+            _ => DelimiterType::Unquoted,
+        },
+        value: Some(start.written_range(proc)),
+    }))
+}
+
 fn handle_whitespace_char_type(c: u8, proc: &mut Processor, metrics: &mut Metrics) -> () {
     proc.write(c);
     metrics.count_whitespace += 1;
@@ -189,6 +245,7 @@ fn handle_whitespace_char_type(c: u8, proc: &mut Processor, metrics: &mut Metric
 // Since the actual processed value would have a length equal or greater to it (e.g. it might be quoted, or some characters might get encoded), we can then read minimum value right to left and start writing from actual processed value length (which is calculated), quoting/encoding as necessary.
 pub fn process_attr_value(proc: &mut Processor, should_collapse_and_trim_ws: bool) -> ProcessingResult<ProcessedAttrValue> {
     let start = WriteCheckpoint::new(proc);
+    let value_start = proc.read_len();
     let src_delimiter = proc.m(IsInLookup(ATTR_QUOTE), Discard).first(proc);
     let delim_lookup = match src_delimiter {
         Some(b'"') => DOUBLE_QUOTE,
@@ -280,7 +337,7 @@ pub fn process_attr_value(proc: &mut Processor, should_collapse_and_trim_ws: boo
         last_char_type = char_type;
     };
     if let Some(c) = src_delimiter {
-        proc.m(IsChar(c), Discard).require("attribute value closing quote")?;
+        proc.m(IsChar(c), Discard).require("attribute value closing quote", "attribute value", value_start)?;
     };
     let minimum_value = start.written_range(proc);
     // If minimum value is empty, return now before trying to read out of range later.
@@ -366,3 +423,34 @@ pub fn process_attr_value(proc: &mut Processor, should_collapse_and_trim_ws: boo
         value: Some(start.written_range(proc)).filter(|r| !r.empty()),
     })
 }
+
+// Encode a decoded attribute value so it can be written as is, including any delimiters. Quotes are always used if
+// `quoted`, as the value could be directly followed by another attribute.
+pub fn encode_attr_value(value: &[u8], quoted: bool) -> Vec<u8> {
+    let delimiter = if !quoted && !value.is_empty() && !ATTR_QUOTE[value[0]] && value.iter().all(|c| !NOT_UNQUOTED_ATTR_VAL_CHAR[*c]) {
+        None
+    } else if value.iter().filter(|c| **c == b'"').count() > value.iter().filter(|c| **c == b'\'').count() {
+        Some(b'\'')
+    } else {
+        Some(b'"')
+    };
+    let mut encoded = Vec::<u8>::with_capacity(value.len() + 2);
+    encoded.extend(delimiter);
+    for (i, &c) in value.iter().enumerate() {
+        let next = value.get(i + 1).copied();
+        if Some(c) == delimiter {
+            encoded.extend_from_slice(ENCODED[&c]);
+            if next.filter(|n| *n == b';' || DIGIT[*n]).is_some() {
+                encoded.push(b';');
+            };
+        } else if c == b'&' && is_entity_start(&value[i..]) {
+            // Prevent an unintentional entity. The semicolon is required, as otherwise it wouldn't be decoded when
+            // followed by an alphanumeric character in an attribute value.
+            encoded.extend_from_slice(b"&amp;");
+        } else {
+            encoded.push(c);
+        };
+    };
+    encoded.extend(delimiter);
+    encoded
+}
@@ -5,7 +5,8 @@ use crate::proc::Processor;
 
 #[inline(always)]
 pub fn process_bang(proc: &mut Processor) -> ProcessingResult<()> {
+    let start = proc.read_len();
     proc.m(IsSeq(b"<!"), Keep).expect();
-    proc.m(ThroughChar(b'>'), Keep).require("bang close")?;
+    proc.m(ThroughChar(b'>'), Keep).require("bang close", "bang", start)?;
     Ok(())
 }
@@ -1,6 +1,7 @@
 use aho_corasick::AhoCorasick;
 use lazy_static::lazy_static;
-use crate::err::ProcessingResult;
+use crate::cfg::Cfg;
+use crate::err::{ErrorType, ProcessingResult};
 use crate::proc::MatchAction::*;
 use crate::proc::MatchMode::*;
 use crate::proc::Processor;
@@ -9,9 +10,78 @@ lazy_static! {
     static ref COMMENT_END: AhoCorasick = AhoCorasick::new(&["-->"]);
 }
 
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum CommentType {
+    Removed,
+    Kept,
+    // Start of a downlevel-hidden conditional comment like `<!--[if IE]>`, with HTML content up to `<![endif]-->`.
+    ConditionalStart,
+}
+
+// Must only be called when the next characters are `<!--`.
+pub fn classify_comment(proc: &mut Processor, cfg: &Cfg) -> CommentType {
+    let starts_with = |proc: &Processor, offset: usize, prefix: &[u8]| proc.peek_many(offset, prefix.len()).filter(|p| *p == prefix).is_some();
+
+    if starts_with(proc, 4, b"[if") {
+        let condition = proc.m(ThroughChar(b'>'), MatchOnly);
+        if proc[condition].ends_with(b"]>") {
+            if starts_with(proc, condition.len(), b"<!-->") {
+                // Start of a downlevel-revealed conditional comment like `<!--[if !IE]><!-->`, which is a complete
+                // comment.
+                return CommentType::Kept;
+            };
+            let comment = proc.m(ThroughSeq(&COMMENT_END), MatchOnly);
+            if proc[comment].ends_with(b"<![endif]-->") {
+                return CommentType::ConditionalStart;
+            };
+        };
+    };
+
+    if starts_with(proc, 4, b"<![endif]") {
+        // End of a downlevel-revealed conditional comment like `<!--<![endif]-->`.
+        return CommentType::Kept;
+    };
+
+    if cfg.keep_comments
+        || cfg.keep_license_comments && starts_with(proc, 4, b"!")
+        || cfg.keep_ssi_comments && starts_with(proc, 4, b"#")
+        || cfg.keep_comment_prefixes.iter().any(|prefix| starts_with(proc, 4, prefix)) {
+        CommentType::Kept
+    } else {
+        CommentType::Removed
+    }
+}
+
 #[inline(always)]
-pub fn process_comment(proc: &mut Processor) -> ProcessingResult<()> {
-    proc.m(IsSeq(b"<!--"), Discard).expect();
-    proc.m(ThroughSeq(&COMMENT_END), Discard).require("comment end")?;
+pub fn process_comment(proc: &mut Processor, keep: bool) -> ProcessingResult<()> {
+    let action = if keep { Keep } else { Discard };
+    let start = proc.read_len();
+    proc.m(IsSeq(b"<!--"), action).expect();
+    if proc.m(ThroughSeq(&COMMENT_END), action).empty() {
+        if !proc.is_lenient() {
+            return Err(ErrorType::NotFound { expected: "comment end", construct: "comment", start });
+        };
+        // Unterminated comments continue until the end.
+        proc.warn(ErrorType::UnexpectedEnd);
+        proc.m(WhileNotSeq(&COMMENT_END), action);
+    };
     Ok(())
 }
+
+// The content of a conditional comment is processed like any other content, as it's HTML in browsers that support
+// conditional comments. It ends at `<![endif]-->`, which can appear anywhere in the content (e.g. after an opening
+// tag whose closing tag is in a later conditional comment).
+#[inline(always)]
+pub fn process_conditional_comment_start(proc: &mut Processor) -> ProcessingResult<()> {
+    let start = proc.read_len();
+    proc.m(IsSeq(b"<!--"), Keep).expect();
+    proc.m(ThroughChar(b'>'), Keep).require("conditional comment condition end", "conditional comment", start)?;
+    proc.set_in_conditional_comment(true);
+    Ok(())
+}
+
+#[inline(always)]
+pub fn process_conditional_comment_end(proc: &mut Processor) -> () {
+    proc.m(IsSeq(b"<![endif]-->"), Keep).expect();
+    proc.set_in_conditional_comment(false);
+}
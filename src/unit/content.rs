@@ -1,26 +1,31 @@
 use crate::cfg::Cfg;
-use crate::err::ProcessingResult;
+use crate::err::{ErrorType, ProcessingResult};
 use crate::gen::codepoints::{TAG_NAME_CHAR, WHITESPACE};
-use crate::proc::checkpoint::ReadCheckpoint;
+use crate::proc::checkpoint::{ReadCheckpoint, WriteCheckpoint};
 use crate::proc::entity::maybe_normalise_entity;
 use crate::proc::MatchAction::*;
 use crate::proc::MatchMode::*;
-use crate::proc::Processor;
+use crate::proc::{Context, Processor};
 use crate::proc::range::ProcessorRange;
 use crate::spec::tag::ns::Namespace;
+use crate::spec::tag::void::VOID_TAGS;
 use crate::spec::tag::omission::{can_omit_as_before, can_omit_as_last_node};
-use crate::spec::tag::whitespace::{get_whitespace_minification_for_tag, WhitespaceMinification};
+use crate::spec::tag::whitespace::{get_whitespace_minification_for_tag, WhitespaceGroup, WhitespaceMinification};
 use crate::unit::bang::process_bang;
-use crate::unit::comment::process_comment;
+use crate::unit::comment::{classify_comment, CommentType, process_comment, process_conditional_comment_end, process_conditional_comment_start};
 use crate::unit::instruction::process_instruction;
-use crate::unit::tag::{MaybeClosingTag, process_tag};
+use crate::unit::tag::{is_tag_start_at, MaybeClosingTag, process_tag, process_tag_end, ProcessedTag};
+use crate::unit::template::{match_name, process_template, template_len};
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 enum ContentType {
     Comment,
+    ConditionalCommentStart,
+    ConditionalCommentEnd,
     Bang,
     Instruction,
     Tag,
+    Template,
 
     Start,
     End,
@@ -28,6 +33,13 @@ enum ContentType {
 }
 
 impl ContentType {
+    fn is_tag_like(&self) -> bool {
+        matches!(self, ContentType::Tag
+            | ContentType::Comment
+            | ContentType::ConditionalCommentStart
+            | ContentType::ConditionalCommentEnd)
+    }
+
     fn peek(proc: &mut Processor) -> ContentType {
         // Manually write out matching for fast performance as this is hot spot; don't use generated trie.
         match proc.peek(0) {
@@ -37,6 +49,7 @@ impl ContentType {
                 Some(b'?') => ContentType::Instruction,
                 Some(b'!') => match proc.peek_many(2, 2) {
                     Some(b"--") => ContentType::Comment,
+                    _ if proc.in_conditional_comment() && proc.peek_many(0, 12) == Some(&b"<![endif]-->"[..]) => ContentType::ConditionalCommentEnd,
                     _ => ContentType::Bang,
                 },
                 Some(c) if TAG_NAME_CHAR[c] => ContentType::Tag,
@@ -47,35 +60,204 @@ impl ContentType {
     }
 }
 
+// Get the lowercase name of the end tag at the next position.
+fn peek_end_tag_name(proc: &Processor) -> Vec<u8> {
+    let mut len = 0;
+    while proc.peek(2 + len).filter(|c| TAG_NAME_CHAR[*c]).is_some() {
+        len += 1;
+    };
+    proc.peek_many(2, len).map_or(Vec::new(), |name| name.to_ascii_lowercase())
+}
+
 pub struct ProcessedContent {
     pub closing_tag_omitted: bool,
+    // Whether the code ended inside a descendant `<plaintext>` element.
+    pub ended_in_plaintext: bool,
+    // If processing was suspended, what was being processed, from innermost to outermost.
+    pub suspended: Option<Vec<Frame>>,
+}
+
+impl ProcessedContent {
+    #[inline(always)]
+    fn suspended(frames: Vec<Frame>) -> ProcessedContent {
+        ProcessedContent { closing_tag_omitted: false, ended_in_plaintext: false, suspended: Some(frames) }
+    }
+}
+
+// State of processing content between nodes.
+#[derive(Copy, Clone)]
+pub struct ContentState {
+    last_written: ContentType,
+    // Whether or not currently in whitespace.
+    ws_skipped: bool,
+    prev_sibling_closing_tag: MaybeClosingTag,
+}
+
+impl ContentState {
+    #[inline(always)]
+    fn start() -> ContentState {
+        ContentState {
+            last_written: ContentType::Start,
+            ws_skipped: false,
+            prev_sibling_closing_tag: MaybeClosingTag::none(),
+        }
+    }
+}
+
+// Something that was being processed when processing was suspended, with everything needed to resume it.
+#[derive(Clone)]
+pub enum Frame {
+    Content {
+        ns: Namespace,
+        parent: Option<ProcessorRange>,
+        descendant_of_pre: bool,
+        state: ContentState,
+    },
+    // The end of an element whose content is the previous frame.
+    TagEnd {
+        parent: Option<ProcessorRange>,
+        tag_name: ProcessorRange,
+        tag_start: usize,
+    },
+}
+
+impl Frame {
+    // The content of the document, which is where processing starts.
+    pub fn document() -> Frame {
+        Frame::Content {
+            ns: Namespace::Html,
+            parent: None,
+            descendant_of_pre: false,
+            state: ContentState::start(),
+        }
+    }
+
+    // How much more could be written than read when resuming this frame, which is the innermost frame.
+    pub fn pending_len(&self) -> usize {
+        match self {
+            Frame::Content { state, .. } => state.ws_skipped as usize + state.prev_sibling_closing_tag.written_len(),
+            Frame::TagEnd { .. } => 0,
+        }
+    }
+
+    // Get this frame for resuming in a processor that starts with `context`.
+    pub fn export(self, proc: &Processor, context: &mut Context) -> Frame {
+        match self {
+            Frame::Content { ns, parent, descendant_of_pre, state } => Frame::Content {
+                ns,
+                parent: parent.map(|r| proc.export_range(r, context)),
+                descendant_of_pre,
+                state: ContentState {
+                    prev_sibling_closing_tag: state.prev_sibling_closing_tag.map(|r| proc.export_range(r, context)),
+                    ..state
+                },
+            },
+            Frame::TagEnd { parent, tag_name, tag_start } => Frame::TagEnd {
+                parent: parent.map(|r| proc.export_range(r, context)),
+                tag_name: proc.export_range(tag_name, context),
+                tag_start: proc.export_position(tag_start, context),
+            },
+        }
+    }
+}
+
+// Resume processing suspended `frames` until the end of the outermost one, unless suspended again.
+pub fn resume(proc: &mut Processor, cfg: &Cfg, frames: Vec<Frame>) -> ProcessingResult<ProcessedContent> {
+    let mut frames = frames.into_iter();
+    let mut content = None;
+    let mut tag = None;
+    while let Some(frame) = frames.next() {
+        match frame {
+            Frame::Content { ns, parent, descendant_of_pre, state } => {
+                let processed = match tag.take() {
+                    Some(processed_tag) => process_content_after_tag(proc, cfg, ns, parent, descendant_of_pre, state, processed_tag)?,
+                    None => process_content_from(proc, cfg, ns, parent, descendant_of_pre, state)?,
+                };
+                if let Some(mut suspended) = processed.suspended {
+                    suspended.extend(frames);
+                    return Ok(ProcessedContent::suspended(suspended));
+                };
+                content = Some(processed);
+            }
+            Frame::TagEnd { parent, tag_name, tag_start } => {
+                tag = Some(process_tag_end(proc, cfg, parent, tag_name, tag_start, content.take().unwrap())?);
+            }
+        };
+    };
+    Ok(content.unwrap())
+}
+
+// Continue processing content after a child element.
+fn process_content_after_tag(proc: &mut Processor, cfg: &Cfg, ns: Namespace, parent: Option<ProcessorRange>, descendant_of_pre: bool, mut state: ContentState, processed: ProcessedTag) -> ProcessingResult<ProcessedContent> {
+    if processed.ended_in_plaintext {
+        return Ok(ProcessedContent {
+            closing_tag_omitted: false,
+            ended_in_plaintext: true,
+            suspended: None,
+        });
+    };
+    state.prev_sibling_closing_tag.replace(processed.closing_tag);
+    state.last_written = ContentType::Tag;
+    process_content_from(proc, cfg, ns, parent, descendant_of_pre, state)
 }
 
 pub fn process_content(proc: &mut Processor, cfg: &Cfg, ns: Namespace, parent: Option<ProcessorRange>, descendant_of_pre: bool) -> ProcessingResult<ProcessedContent> {
-    let &WhitespaceMinification { collapse, destroy_whole, trim } = get_whitespace_minification_for_tag(parent.map(|r| &proc[r]), descendant_of_pre);
+    process_content_from(proc, cfg, ns, parent, descendant_of_pre, ContentState::start())
+}
+
+fn process_content_from(proc: &mut Processor, cfg: &Cfg, ns: Namespace, parent: Option<ProcessorRange>, descendant_of_pre: bool, state: ContentState) -> ProcessingResult<ProcessedContent> {
+    let &WhitespaceMinification { collapse, destroy_whole, trim } = get_whitespace_minification_for_tag(cfg, parent.map(|r| &proc[r]), descendant_of_pre);
 
     let handle_ws = collapse || destroy_whole || trim;
 
-    let mut last_written = ContentType::Start;
-    // Whether or not currently in whitespace.
-    let mut ws_skipped = false;
-    let mut prev_sibling_closing_tag = MaybeClosingTag::none();
+    let ContentState { mut last_written, mut ws_skipped, mut prev_sibling_closing_tag } = state;
 
     loop {
         // WARNING: Do not write anything until any previously ignored whitespace has been processed later.
 
-        // Process comments, bangs, and instructions, which are completely ignored and do not affect anything (previous
-        // element node's closing tag, unintentional entities, whitespace, etc.).
-        let next_content_type = ContentType::peek(proc);
+        // Process removed comments, bangs, and instructions, which are completely ignored and do not affect anything
+        // (previous element node's closing tag, unintentional entities, whitespace, etc.). Kept comments are treated
+        // like tags.
+        let mut next_content_type = ContentType::peek(proc);
+        if !cfg.template_delimiters.is_empty() {
+            if template_len(proc, cfg, 0).is_some() {
+                next_content_type = ContentType::Template;
+            } else if proc.peek(0) == Some(b'<') && template_len(proc, cfg, 1).is_some() {
+                // Tag name starts with a template, e.g. `<{{ tag }}>`.
+                next_content_type = ContentType::Tag;
+            };
+        };
         match next_content_type {
-            ContentType::Comment => {
-                process_comment(proc)?;
-                continue;
-            }
+            ContentType::Comment => match classify_comment(proc, cfg) {
+                CommentType::Removed => {
+                    process_comment(proc, false)?;
+                    continue;
+                }
+                CommentType::Kept => {}
+                CommentType::ConditionalStart => next_content_type = ContentType::ConditionalCommentStart,
+            },
             ContentType::Bang => {
                 process_bang(proc)?;
                 continue;
             }
+            ContentType::End if proc.is_lenient() && !proc.at_end() && !proc.is_open_element(&peek_end_tag_name(proc)) => {
+                // Browsers ignore end tags that don't match any open element, except for `</p>` and `</br>`, which
+                // are treated as elements and so are kept as is.
+                let name = peek_end_tag_name(proc);
+                proc.warn(if VOID_TAGS.contains(name.as_slice()) {
+                    ErrorType::VoidElementClosingTag(unsafe { String::from_utf8_unchecked(name) })
+                } else {
+                    ErrorType::UnexpectedClosingTag
+                });
+                let keep = is_tag_start_at(proc, 0, b"</p") || is_tag_start_at(proc, 0, b"</br");
+                if keep {
+                    prev_sibling_closing_tag.write_if_exists(proc);
+                };
+                let action = if keep { Keep } else { Discard };
+                proc.m(WhileNotChar(b'>'), action);
+                proc.m(IsChar(b'>'), action);
+                continue;
+            }
             ContentType::Instruction => {
                 process_instruction(proc)?;
                 continue;
@@ -83,8 +265,22 @@ pub fn process_content(proc: &mut Processor, cfg: &Cfg, ns: Namespace, parent: O
             _ => {}
         };
 
+        // Suspend before an element, with any whitespace before it still pending. Don't if a `<` text character was
+        // just written, as it's rewritten if the element is removed and the next character would make it a tag.
+        if next_content_type == ContentType::Tag && !proc.last_is(b'<') && proc.should_suspend() {
+            return Ok(ProcessedContent::suspended(vec![Frame::Content {
+                ns,
+                parent,
+                descendant_of_pre,
+                state: ContentState { last_written, ws_skipped, prev_sibling_closing_tag },
+            }]));
+        };
+
         maybe_normalise_entity(proc, false);
 
+        // What was last written before a space written for previously ignored whitespace, in case the following
+        // element is removed and the whitespace should be pending again.
+        let mut before_ws = None;
         if handle_ws {
             if next_content_type == ContentType::Text && proc.m(IsInLookup(WHITESPACE), Discard).nonempty() {
                 // This is the start or part of one or more whitespace characters.
@@ -95,8 +291,8 @@ pub fn process_content(proc: &mut Processor, cfg: &Cfg, ns: Namespace, parent: O
 
             // Next character is not whitespace, so handle any previously ignored whitespace.
             if ws_skipped {
-                if destroy_whole && last_written == ContentType::Tag && next_content_type == ContentType::Tag {
-                    // Whitespace is between two tags, instructions, or bangs.
+                if destroy_whole && last_written.is_tag_like() && next_content_type.is_tag_like() {
+                    // Whitespace is between two tags or kept comments.
                     // `destroy_whole` is on, so don't write it.
                 } else if trim && (last_written == ContentType::Start || next_content_type == ContentType::End) {
                     // Whitespace is leading or trailing.
@@ -107,6 +303,7 @@ pub fn process_content(proc: &mut Processor, cfg: &Cfg, ns: Namespace, parent: O
                     prev_sibling_closing_tag.write_if_exists(proc);
                     // Current contiguous whitespace needs to be reduced to a single space character.
                     proc.write(b' ');
+                    before_ws = Some(last_written);
                     last_written = ContentType::Text;
                 } else {
                     unreachable!();
@@ -121,9 +318,13 @@ pub fn process_content(proc: &mut Processor, cfg: &Cfg, ns: Namespace, parent: O
         match next_content_type {
             ContentType::Tag => {
                 let tag_checkpoint = ReadCheckpoint::new(proc);
+                let tag_start = proc.read_len();
                 proc.skip_expect();
-                let tag_name = proc.m(WhileInLookup(TAG_NAME_CHAR), Discard).require("tag name")?;
-                proc.make_lowercase(tag_name);
+                let (tag_name, has_template) = match_name(proc, cfg, TAG_NAME_CHAR, Discard);
+                tag_name.require("tag name", "tag", tag_start)?;
+                if !has_template {
+                    proc.make_lowercase(tag_name);
+                };
 
                 if can_omit_as_before(proc, parent, tag_name) {
                     // TODO Is this necessary? Can a previous closing tag even exist?
@@ -131,11 +332,56 @@ pub fn process_content(proc: &mut Processor, cfg: &Cfg, ns: Namespace, parent: O
                     tag_checkpoint.restore(proc);
                     return Ok(ProcessedContent {
                         closing_tag_omitted: true,
+                        ended_in_plaintext: false,
+                        suspended: None,
                     });
                 };
 
-                let new_closing_tag = process_tag(proc, cfg, ns, parent, descendant_of_pre || ns == Namespace::Html && parent.filter(|p| &proc[*p] == b"pre").is_some(), prev_sibling_closing_tag, tag_name)?;
-                prev_sibling_closing_tag.replace(new_closing_tag);
+                // Whitespace sensitive elements in `cfg.whitespace_groups` are like `pre`, so their descendants' whitespace
+                // is left as is too.
+                let parent_is_pre = parent.filter(|p| {
+                    ns == Namespace::Html && &proc[*p] == b"pre" || cfg.whitespace_groups.get(&proc[*p]) == Some(&WhitespaceGroup::WhitespaceSensitive)
+                }).is_some();
+                let tag_write_checkpoint = WriteCheckpoint::new(proc);
+                let processed = process_tag(proc, cfg, ns, parent, descendant_of_pre || parent_is_pre, prev_sibling_closing_tag, tag_name)?;
+                if let Some(mut suspended) = processed.suspended {
+                    suspended.push(Frame::Content {
+                        ns,
+                        parent,
+                        descendant_of_pre,
+                        state: ContentState { last_written, ws_skipped, prev_sibling_closing_tag },
+                    });
+                    return Ok(ProcessedContent::suspended(suspended));
+                };
+                if processed.ended_in_plaintext {
+                    return Ok(ProcessedContent {
+                        closing_tag_omitted: false,
+                        ended_in_plaintext: true,
+                        suspended: None,
+                    });
+                };
+                prev_sibling_closing_tag.replace(processed.closing_tag);
+                if let Some(before_ws) = before_ws.filter(|_| tag_write_checkpoint.written_count(proc) == 0) {
+                    // The element was removed, so the whitespace before it is still ignored and joins any after it.
+                    proc.undo_write(1);
+                    ws_skipped = true;
+                    last_written = before_ws;
+                    continue;
+                };
+            }
+            ContentType::Comment => {
+                // The comment is a node between the previous sibling element and whatever follows, so its closing tag
+                // can't be omitted.
+                prev_sibling_closing_tag.write_if_exists(proc);
+                process_comment(proc, true)?;
+            }
+            ContentType::ConditionalCommentStart => {
+                prev_sibling_closing_tag.write_if_exists(proc);
+                process_conditional_comment_start(proc)?;
+            }
+            ContentType::ConditionalCommentEnd => {
+                prev_sibling_closing_tag.write_if_exists(proc);
+                process_conditional_comment_end(proc);
             }
             ContentType::End => {
                 if prev_sibling_closing_tag.exists_and(|prev_tag| !can_omit_as_last_node(proc, parent, prev_tag)) {
@@ -143,6 +389,11 @@ pub fn process_content(proc: &mut Processor, cfg: &Cfg, ns: Namespace, parent: O
                 };
                 break;
             }
+            ContentType::Template => {
+                // Templates could render to anything, so treat them like text.
+                prev_sibling_closing_tag.write_if_exists(proc);
+                process_template(proc, cfg);
+            }
             ContentType::Text => {
                 // Immediate next sibling node is not an element, so write any immediate previous sibling element's closing tag.
                 if prev_sibling_closing_tag.exists() {
@@ -170,16 +421,25 @@ pub fn process_content(proc: &mut Processor, cfg: &Cfg, ns: Namespace, parent: O
                     proc.write_slice(b"&LT");
                 };
 
-                proc.accept_expect();
+                if c == b'>' && proc.in_conditional_comment() && (proc.last_is(b'-') || proc.last_is(b'!')) {
+                    // Browsers that don't support conditional comments treat the entire conditional comment as a
+                    // comment, so don't let `-->` or `--!>` form and end it early.
+                    proc.skip_expect();
+                    proc.write_slice(b"&gt");
+                } else {
+                    proc.accept_expect();
+                };
             }
             _ => unreachable!(),
         };
 
-        // This should not be reached if ContentType::{Comment, End}.
+        // This should not be reached if ContentType::End.
         last_written = next_content_type;
     };
 
     Ok(ProcessedContent {
         closing_tag_omitted: false,
+        ended_in_plaintext: false,
+        suspended: None,
     })
 }
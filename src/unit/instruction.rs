@@ -11,7 +11,8 @@ lazy_static! {
 
 #[inline(always)]
 pub fn process_instruction(proc: &mut Processor) -> ProcessingResult<()> {
+    let start = proc.read_len();
     proc.m(IsSeq(b"<?"), Keep).expect();
-    proc.m(ThroughSeq(&INSTRUCTION_END), Keep).require("instruction end")?;
+    proc.m(ThroughSeq(&INSTRUCTION_END), Keep).require("instruction end", "instruction", start)?;
     Ok(())
 }
@@ -3,6 +3,9 @@ pub mod bang;
 pub mod comment;
 pub mod content;
 pub mod instruction;
+pub mod rawtext;
+pub mod rcdata;
 pub mod script;
 pub mod style;
 pub mod tag;
+pub mod template;
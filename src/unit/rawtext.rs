@@ -0,0 +1,28 @@
+use crate::proc::Processor;
+use crate::unit::tag::is_tag_start_at;
+
+// RAWTEXT content (e.g. `<xmp>`) has no entities or tags, and ends at the first end tag with the same name.
+// See https://html.spec.whatwg.org/multipage/parsing.html#rawtext-state.
+#[inline(always)]
+pub fn process_rawtext(proc: &mut Processor, end: &'static [u8]) -> () {
+    let mut len = 0;
+    while proc.peek(len).filter(|c| *c != b'<' || !is_tag_start_at(proc, len, end)).is_some() {
+        len += 1;
+    };
+    if len > 0 {
+        proc.accept_amount_expect(len);
+    };
+    // `process_tag` will require closing tag.
+}
+
+// Everything after a `<plaintext>` start tag is text.
+#[inline(always)]
+pub fn process_plaintext(proc: &mut Processor) -> () {
+    let mut len = 0;
+    while proc.peek(len).is_some() {
+        len += 1;
+    };
+    if len > 0 {
+        proc.accept_amount_expect(len);
+    };
+}
@@ -0,0 +1,53 @@
+use crate::cfg::Cfg;
+use crate::gen::codepoints::WHITESPACE;
+use crate::proc::entity::{maybe_normalise_entity, peek_entity_ascii};
+use crate::proc::Processor;
+use crate::proc::range::ProcessorRange;
+use crate::spec::tag::whitespace::{get_whitespace_minification_for_tag, WhitespaceMinification};
+use crate::unit::tag::is_tag_start_at;
+
+// RCDATA content (e.g. `<textarea>`) has entities but no tags, and ends at the first end tag with the same name.
+// See https://html.spec.whatwg.org/multipage/parsing.html#rcdata-state.
+pub fn process_rcdata(proc: &mut Processor, cfg: &Cfg, end: &'static [u8], tag_name: ProcessorRange, descendant_of_pre: bool) -> () {
+    let &WhitespaceMinification { collapse, trim, .. } = get_whitespace_minification_for_tag(cfg, Some(&proc[tag_name]), descendant_of_pre);
+
+    // Whether currently in whitespace that has been skipped.
+    let mut ws_skipped = false;
+    let mut last_written: Option<u8> = None;
+    // Whether the last written characters are `</` followed by zero or more alphanumeric characters, which could become
+    // the end tag if an entity is decoded.
+    let mut in_possible_end_tag = false;
+    loop {
+        if proc.at_end() || is_tag_start_at(proc, 0, end) {
+            break;
+        };
+
+        // Don't decode entities that could form the end tag.
+        if !in_possible_end_tag && peek_entity_ascii(proc).filter(|c| *c == b'<' || *c == b'/').is_none() {
+            maybe_normalise_entity(proc, false);
+        };
+
+        let c = proc.peek(0).unwrap();
+        if collapse && WHITESPACE[c] {
+            proc.skip_expect();
+            ws_skipped = true;
+            continue;
+        };
+        if ws_skipped {
+            if !(trim && last_written.is_none()) {
+                proc.write(b' ');
+                last_written = Some(b' ');
+            };
+            ws_skipped = false;
+        };
+
+        in_possible_end_tag = c == b'/' && last_written == Some(b'<') || in_possible_end_tag && c.is_ascii_alphanumeric();
+        proc.accept_expect();
+        last_written = Some(c);
+    };
+
+    if ws_skipped && !trim {
+        proc.write(b' ');
+    };
+    // `process_tag` will require closing tag.
+}
@@ -1,83 +1,127 @@
 use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
 use lazy_static::lazy_static;
 
-#[cfg(feature = "js-esbuild")]
-use {
-    crate::proc::checkpoint::WriteCheckpoint,
-    crate::proc::EsbuildSection,
-    esbuild_rs::{TransformOptions, TransformOptionsBuilder},
-    std::sync::Arc,
-};
-
 use crate::cfg::Cfg;
 use crate::err::ProcessingResult;
+use crate::minifier::CodeKind;
+use crate::proc::checkpoint::WriteCheckpoint;
 use crate::proc::MatchAction::*;
 use crate::proc::MatchMode::*;
-use crate::proc::Processor;
+use crate::proc::{MinifiedSection, MinifiedSectionResult, Processor};
+use crate::unit::tag::is_tag_start_at;
 
-#[cfg(feature = "js-esbuild")]
 lazy_static! {
-    static ref TRANSFORM_OPTIONS: Arc<TransformOptions> = {
-        let mut builder = TransformOptionsBuilder::new();
-        builder.minify_identifiers = true;
-        builder.minify_syntax = true;
-        builder.minify_whitespace = true;
-        builder.build()
-    };
+    static ref SCRIPT_END: AhoCorasick = AhoCorasickBuilder::new().ascii_case_insensitive(true).build(&["</script"]);
 }
 
-lazy_static! {
-    static ref SCRIPT_END: AhoCorasick = AhoCorasickBuilder::new().ascii_case_insensitive(true).build(&["</script"]);
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum ScriptDataState {
+    Normal,
+    Escaped,
+    DoubleEscaped,
+}
+
+// Returns the length of script data until its end tag or the end of the code, following the state transitions
+// described in `notes/Script data.md`, and whether it contains any HTML comments.
+fn escaped_script_data_len(proc: &Processor) -> (usize, bool) {
+    let mut state = ScriptDataState::Normal;
+    let mut has_comment = false;
+    let mut len = 0;
+    loop {
+        match (state, proc.peek(len)) {
+            (_, None) => break,
+            (ScriptDataState::Normal, Some(b'<')) | (ScriptDataState::Escaped, Some(b'<')) if is_tag_start_at(proc, len, b"</script") => break,
+            (ScriptDataState::DoubleEscaped, Some(b'<')) if is_tag_start_at(proc, len, b"</script") => {
+                state = ScriptDataState::Escaped;
+                len += 8;
+            }
+            (ScriptDataState::Escaped, Some(b'<')) if is_tag_start_at(proc, len, b"<script") => {
+                state = ScriptDataState::DoubleEscaped;
+                len += 7;
+            }
+            (ScriptDataState::Normal, Some(b'<')) if proc.peek_many(len, 4) == Some(b"<!--") => {
+                state = ScriptDataState::Escaped;
+                has_comment = true;
+                // Only skip `<!` as the following `--` can be part of `-->` (e.g. `<!-->`).
+                len += 2;
+            }
+            (ScriptDataState::Escaped, Some(b'-')) | (ScriptDataState::DoubleEscaped, Some(b'-')) if proc.peek_many(len, 3) == Some(b"-->") => {
+                state = ScriptDataState::Normal;
+                len += 3;
+            }
+            _ => len += 1,
+        };
+    };
+    (len, has_comment)
 }
 
 #[inline(always)]
-pub fn process_script(proc: &mut Processor, cfg: &Cfg, js: bool) -> ProcessingResult<()> {
-    #[cfg(feature = "js-esbuild")]
-        let start = WriteCheckpoint::new(proc);
+pub fn process_script(proc: &mut Processor, cfg: &Cfg, kind: Option<CodeKind>) -> ProcessingResult<()> {
+    let start = WriteCheckpoint::new(proc);
+    let source_start = proc.read_len();
     proc.require_not_at_end()?;
-    proc.m(WhileNotSeq(&SCRIPT_END), Keep);
+    let has_comment = if cfg.parse_escaped_script_data {
+        let (len, has_comment) = escaped_script_data_len(proc);
+        if len > 0 {
+            proc.accept_amount_expect(len);
+        };
+        has_comment
+    } else {
+        proc.m(WhileNotSeq(&SCRIPT_END), Keep);
+        false
+    };
     // `process_tag` will require closing tag.
 
     // TODO This is copied from style.rs.
-    #[cfg(feature = "js-esbuild")]
-    if js && cfg.minify_js {
-        let (wg, results) = proc.new_esbuild_section();
-        let src = start.written_range(proc);
-        unsafe {
-            esbuild_rs::transform_direct_unmanaged(&proc[src], &TRANSFORM_OPTIONS.clone(), move |result| {
-                let mut guard = results.lock().unwrap();
-                // TODO Handle other forms:
-                // 1 < /script/.exec(a).length
-                // `  ${`  ${a</script/}  `}  `
-                // // </script>
-                // /* </script>
-                // Considerations:
-                // - Need to parse strings (e.g. "", '', ``) so syntax within strings aren't mistakenly interpreted as code.
-                // - Need to be able to parse regex literals to determine string delimiters aren't actually characters in the regex.
-                // - Determining whether a slash is division or regex requires a full-blown JS parser to handle all cases (this is a well-known JS parsing problem).
-                // - `/</script` or `/</ script` are not valid JS so don't need to be handled.
-                let mut escaped = Vec::<u8>::new();
-                // SCRIPT_END must be case insensitive.
-                SCRIPT_END.replace_all_with_bytes(
-                    result.code.as_str().trim().as_bytes(),
-                    &mut escaped,
-                    |_, orig, dst| {
-                        dst.extend(b"<\\/");
-                        // Keep original case.
-                        dst.extend(&orig[2..]);
-                        true
-                    },
-                );
-                guard.push(EsbuildSection {
-                    src,
-                    escaped,
-                });
-                // Drop Arc reference and Mutex guard before marking task as complete as it's possible proc::finish
-                // waiting on WaitGroup will resume before Arc/Mutex is dropped after exiting this function.
-                drop(guard);
-                drop(results);
-                drop(wg);
-            });
+    // Minified code could form an HTML comment and `<script` that changes where the script ends.
+    let minifier = match kind {
+        Some(CodeKind::Js) | Some(CodeKind::JsModule) if cfg.minify_js => cfg.content_minifier(),
+        Some(CodeKind::Json) if cfg.minify_json => Some(cfg.json_minifier()),
+        _ => None,
+    };
+    if let (Some(kind), false) = (kind, has_comment) {
+        if let Some(minifier) = minifier {
+            let send = proc.new_minified_section();
+            let src = start.written_range(proc);
+            let source_end = proc.read_len();
+            let policy = cfg.on_minify_failure;
+            cfg.minify_async(minifier, kind, proc[src].to_vec(), Box::new(move |result| {
+                let section = match result {
+                    Ok(min_code) => {
+                        // TODO Handle other forms:
+                        // 1 < /script/.exec(a).length
+                        // `  ${`  ${a</script/}  `}  `
+                        // // </script>
+                        // /* </script>
+                        // Considerations:
+                        // - Need to parse strings (e.g. "", '', ``) so syntax within strings aren't mistakenly interpreted as code.
+                        // - Need to be able to parse regex literals to determine string delimiters aren't actually characters in the regex.
+                        // - Determining whether a slash is division or regex requires a full-blown JS parser to handle all cases (this is a well-known JS parsing problem).
+                        // - `/</script` or `/</ script` are not valid JS so don't need to be handled.
+                        let mut escaped = Vec::<u8>::new();
+                        // SCRIPT_END must be case insensitive.
+                        SCRIPT_END.replace_all_with_bytes(
+                            &min_code,
+                            &mut escaped,
+                            |_, orig, dst| {
+                                dst.extend(b"<\\/");
+                                // Keep original case.
+                                dst.extend(&orig[2..]);
+                                true
+                            },
+                        );
+                        Some(MinifiedSection {
+                            src,
+                            result: MinifiedSectionResult::Escaped(escaped),
+                        })
+                    }
+                    Err(err) => MinifiedSection::failed(src, source_start, source_end, kind, err, policy),
+                };
+                if let Some(section) = section {
+                    // The receiver is only dropped after all senders have been dropped.
+                    send.send(section).unwrap();
+                };
+            }));
         };
     };
 
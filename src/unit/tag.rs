@@ -8,14 +8,25 @@ use crate::proc::Processor;
 use crate::proc::range::ProcessorRange;
 use crate::spec::tag::void::VOID_TAGS;
 use crate::unit::attr::{AttrType, process_attr, ProcessedAttr};
-use crate::unit::content::process_content;
+use crate::unit::content::{Frame, process_content, ProcessedContent};
 use crate::unit::script::process_script;
+use crate::unit::rawtext::{process_plaintext, process_rawtext};
+use crate::unit::rcdata::process_rcdata;
 use crate::unit::style::process_style;
+use crate::unit::template::match_name;
 use crate::gen::attrs::{ATTRS, AttributeMinification};
 use crate::spec::tag::ns::Namespace;
-use crate::gen::codepoints::{TAG_NAME_CHAR, WHITESPACE};
+use crate::gen::codepoints::{ATTR_QUOTE, TAG_NAME_CHAR, WHITESPACE};
 use crate::cfg::Cfg;
+use crate::hook::HookAction;
+use crate::tokenizer::{Token, Tokenizer};
+use crate::minifier::CodeKind;
+use crate::proc::entity::decode_entities;
+use crate::unit::attr::minify::{AttrCode, minify_attr};
+use crate::unit::attr::value::encode_attr_value;
 use crate::spec::tag::omission::{can_omit_as_last_node, can_omit_as_before};
+use crate::spec::tag::text::{get_text_content, TextContent};
+use crate::spec::tag::whitespace::is_whitespace_sensitive_attr;
 
 lazy_static! {
     pub static ref JAVASCRIPT_MIME_TYPES: HashSet<&'static [u8]> = {
@@ -38,16 +49,62 @@ lazy_static! {
         s.insert(b"text/x-javascript");
         s
     };
+
+    pub static ref JSON_SCRIPT_TYPES: HashSet<&'static [u8]> = {
+        let mut s = HashSet::<&'static [u8]>::new();
+        s.insert(b"application/json");
+        s.insert(b"application/ld+json");
+        s.insert(b"importmap");
+        s.insert(b"speculationrules");
+        s
+    };
 }
 
 #[derive(Copy, Clone)]
 enum TagType {
     ScriptJs,
+    ScriptJsModule,
+    ScriptJson,
     ScriptData,
     Style,
+    // Contains the end tag start sequence, e.g. `</textarea`.
+    RcData(&'static [u8]),
+    RawText(&'static [u8]),
+    PlainText,
     Other,
 }
 
+// Whether the next characters after `offset` are `seq` (case insensitive) followed by a character that ends a tag name.
+// `seq` should be the start of a tag, such as `</script`.
+pub fn is_tag_start_at(proc: &Processor, offset: usize, seq: &[u8]) -> bool {
+    proc.peek_many(offset, seq.len()).filter(|s| s.eq_ignore_ascii_case(seq)).is_some()
+        && proc.peek(offset + seq.len()).filter(|c| WHITESPACE[*c] || *c == b'/' || *c == b'>').is_some()
+}
+
+// Whether an attribute with this (lowercase) name is an event handler, like `onclick`.
+fn is_event_handler_attr(name: &[u8]) -> bool {
+    name.len() > 2 && name.starts_with(b"on") && name.iter().all(|c| c.is_ascii_lowercase())
+}
+
+// Get the written value of an attribute without any quotes and with entities decoded.
+fn decode_attr_value(proc: &Processor, value: ProcessorRange, typ: AttrType) -> Vec<u8> {
+    let raw = &proc[value];
+    decode_entities(if typ == AttrType::Quoted { &raw[1..raw.len() - 1] } else { raw }, true)
+}
+
+// Whether the element with the tag name just read should be removed according to `cfg.remove_elements`.
+fn is_removed_element(proc: &Processor, cfg: &Cfg, source_tag_name: ProcessorRange) -> bool {
+    if cfg.remove_elements.is_empty() {
+        return false;
+    };
+    // The start tag hasn't been written yet, so it can be parsed from the source to match its attributes.
+    let tag_start = proc.read_len() - source_tag_name.len() - 1;
+    match Tokenizer::new(proc.source_from(tag_start)).next() {
+        Some(Ok(Token::StartTag(tag))) => cfg.remove_elements.iter().any(|s| s.matches(&tag)),
+        _ => false,
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct MaybeClosingTag(Option<ProcessorRange>);
 
@@ -91,6 +148,43 @@ impl MaybeClosingTag {
     pub fn replace(&mut self, tag: MaybeClosingTag) -> () {
         self.0 = tag.0;
     }
+
+    // How long the closing tag would be when written.
+    #[inline(always)]
+    pub fn written_len(&self) -> usize {
+        self.0.map_or(0, |tag| tag.len() + 3)
+    }
+
+    #[inline(always)]
+    pub fn map<F: FnOnce(ProcessorRange) -> ProcessorRange>(self, f: F) -> MaybeClosingTag {
+        MaybeClosingTag(self.0.map(f))
+    }
+}
+
+pub struct ProcessedTag {
+    pub closing_tag: MaybeClosingTag,
+    // Whether the code ended inside a `<plaintext>` element, which is this element or one of its descendants. All
+    // ancestors are then implicitly closed, as there can't be any more end tags.
+    pub ended_in_plaintext: bool,
+    // If processing was suspended, what was being processed, from innermost to outermost.
+    pub suspended: Option<Vec<Frame>>,
+}
+
+impl ProcessedTag {
+    #[inline(always)]
+    fn closing_tag(closing_tag: MaybeClosingTag) -> ProcessedTag {
+        ProcessedTag { closing_tag, ended_in_plaintext: false, suspended: None }
+    }
+
+    #[inline(always)]
+    fn ended_in_plaintext() -> ProcessedTag {
+        ProcessedTag { closing_tag: MaybeClosingTag(None), ended_in_plaintext: true, suspended: None }
+    }
+
+    #[inline(always)]
+    fn suspended(frames: Vec<Frame>) -> ProcessedTag {
+        ProcessedTag { closing_tag: MaybeClosingTag(None), ended_in_plaintext: false, suspended: Some(frames) }
+    }
 }
 
 // TODO Comment param `prev_sibling_closing_tag`.
@@ -102,25 +196,73 @@ pub fn process_tag(
     descendant_of_pre: bool,
     mut prev_sibling_closing_tag: MaybeClosingTag,
     source_tag_name: ProcessorRange,
-) -> ProcessingResult<MaybeClosingTag> {
+) -> ProcessingResult<ProcessedTag> {
+    let action = if is_removed_element(proc, cfg, source_tag_name) {
+        HookAction::Drop
+    } else {
+        cfg.hook.as_ref().map_or(HookAction::Keep, |hook| hook.element(&proc[source_tag_name]))
+    };
+    if action != HookAction::Keep {
+        if let HookAction::Replace(_) = action {
+            prev_sibling_closing_tag.write_if_exists(proc);
+        };
+        // The element still needs to be processed to find where it ends, but nothing in it should be minified or
+        // hooked as it will be removed.
+        let element_checkpoint = WriteCheckpoint::new(proc);
+        let inner_cfg = Cfg {
+            minify_js: false,
+            minify_css: false,
+            minify_json: false,
+            remove_elements: Vec::new(),
+            hook: None,
+            ..cfg.clone()
+        };
+        // Processing can't be suspended inside the element, as what's been written so far will be erased.
+        proc.set_suspendable(false);
+        let inner = process_tag(proc, &inner_cfg, ns, parent, descendant_of_pre, MaybeClosingTag::none(), source_tag_name)?;
+        proc.set_suspendable(true);
+        element_checkpoint.erase_written(proc);
+        return match action {
+            HookAction::Replace(code) => {
+                proc.make_room(code.len())?;
+                proc.write_slice(&code);
+                Ok(ProcessedTag { closing_tag: MaybeClosingTag(None), ..inner })
+            }
+            // Nothing was written, so the previous sibling's closing tag is still pending.
+            _ => Ok(ProcessedTag { closing_tag: prev_sibling_closing_tag, ..inner }),
+        };
+    };
+
     if prev_sibling_closing_tag.exists_and(|prev_tag| !can_omit_as_before(proc, Some(prev_tag), source_tag_name)) {
         prev_sibling_closing_tag.write(proc);
     };
+    // Position of `<` in the source code.
+    let tag_start = proc.read_len() - source_tag_name.len() - 1;
     // Write initially skipped left chevron.
     proc.write(b'<');
     // Write previously skipped name and use written code as range (otherwise source code will eventually be overwritten).
     let tag_name = proc.write_range(source_tag_name);
 
-    let mut tag_type = match &proc[tag_name] {
+    let mut tag_type = match get_text_content(ns, &proc[tag_name]) {
         // Unless non-JS MIME `type` is provided, `script` tags contain JS.
-        b"script" => TagType::ScriptJs,
-        b"style" => TagType::Style,
-        _ => TagType::Other,
+        Some(TextContent::Script) => TagType::ScriptJs,
+        Some(TextContent::Style) => TagType::Style,
+        Some(TextContent::RcData(end)) => TagType::RcData(end),
+        Some(TextContent::RawText(end)) => TagType::RawText(end),
+        Some(TextContent::PlainText) => TagType::PlainText,
+        None => TagType::Other,
     };
 
     let mut last_attr_type: Option<AttrType> = None;
     let mut self_closing = false;
     let is_void_tag = VOID_TAGS.contains(&proc[tag_name]);
+    // Whether whitespace in the element's content should be kept as is, which is inherited by all descendants.
+    let mut preserve_whitespace = descendant_of_pre;
+    // The last written attribute's value to minify, which is only minified once it's known whether another attribute
+    // follows it.
+    let mut pending_attr_code: Option<(AttrCode, CodeKind)> = None;
+    // Names of attributes seen so far, to detect duplicates. Only tracked in lenient mode.
+    let mut attr_names = Vec::<Vec<u8>>::new();
 
     loop {
         // At the beginning of this loop, the last parsed unit was either the tag name or an attribute (including its value, if it had one).
@@ -153,7 +295,48 @@ pub fn process_tag(
             _ => {}
         };
 
-        let ProcessedAttr { name, typ, value } = process_attr(proc, ns, tag_name)?;
+        let attr_start = proc.read_len();
+        let ProcessedAttr { name, mut typ, mut value, templated } = process_attr(proc, cfg, ns, tag_name)?;
+        if proc.is_lenient() {
+            // Browsers ignore all but the first attribute with the same name.
+            if attr_names.iter().any(|n| n.as_slice() == &proc[name]) {
+                proc.warn_at(attr_start, ErrorType::DuplicateAttribute(String::from_utf8_lossy(&proc[name]).into_owned()));
+            } else {
+                attr_names.push(proc[name].to_vec());
+            };
+        };
+        if cfg.is_removed_attribute(&proc[name]) {
+            attr_checkpoint.erase_written(proc);
+            continue;
+        };
+        if let Some(hook) = &cfg.hook {
+            let decoded = value.map(|v| decode_attr_value(proc, v, typ));
+            match hook.attribute(&proc[tag_name], &proc[name], decoded.as_deref()) {
+                HookAction::Keep => {}
+                HookAction::Drop => {
+                    attr_checkpoint.erase_written(proc);
+                    continue;
+                }
+                HookAction::Replace(new_value) => {
+                    // Rewrite everything after the name, which is a range in the written code so must be kept.
+                    let written = attr_checkpoint.written_range(proc);
+                    let name_end = (proc[written][0] == b' ') as usize + name.len();
+                    proc.undo_write(written.len() - name_end);
+                    let encoded = if new_value.is_empty() { Vec::new() } else { encode_attr_value(&new_value, false) };
+                    proc.make_room(encoded.len() + 1)?;
+                    if encoded.is_empty() {
+                        typ = AttrType::NoValue;
+                        value = None;
+                    } else {
+                        proc.write(b'=');
+                        let value_checkpoint = WriteCheckpoint::new(proc);
+                        proc.write_slice(&encoded);
+                        typ = if ATTR_QUOTE[encoded[0]] { AttrType::Quoted } else { AttrType::Unquoted };
+                        value = Some(value_checkpoint.written_range(proc));
+                    };
+                }
+            };
+        };
         match (tag_type, &proc[name]) {
             // NOTE: We don't support multiple `type` attributes, so can't go from ScriptData => ScriptJs.
             (TagType::ScriptJs, b"type") => {
@@ -163,6 +346,13 @@ pub fn process_tag(
                     .is_none();
                 if script_tag_type_is_js {
                     erase_attr = true;
+                } else if value.filter(|v| &proc[*v] == b"module").is_some() {
+                    // Module scripts can't have their `type` removed.
+                    tag_type = TagType::ScriptJsModule;
+                } else if value.filter(|v| cfg.js_mime_types.iter().any(|t| t.as_slice() == &proc[*v])).is_some() {
+                    // Keep the `type` as browsers don't consider it to be JS.
+                } else if value.filter(|v| JSON_SCRIPT_TYPES.contains(&proc[*v])).is_some() {
+                    tag_type = TagType::ScriptJson;
                 } else {
                     // Tag does not contain JS, don't minify JS.
                     tag_type = TagType::ScriptData;
@@ -177,12 +367,42 @@ pub fn process_tag(
                 };
             }
         };
+        if cfg.infer_whitespace_sensitivity && !templated && !preserve_whitespace {
+            let decoded = value.map(|v| decode_attr_value(proc, v, typ));
+            preserve_whitespace = is_whitespace_sensitive_attr(&proc[name], decoded.as_deref());
+        };
+        let mut attr_code = None;
+        if !erase_attr && !templated {
+            if let Some(value) = value {
+                let kind = if cfg.minify_css && &proc[name] == b"style" {
+                    Some(CodeKind::CssDeclarations)
+                } else if cfg.minify_js && ns == Namespace::Html && is_event_handler_attr(&proc[name]) {
+                    Some(CodeKind::JsFunctionBody)
+                } else {
+                    None
+                };
+                attr_code = kind.map(|kind| (AttrCode {
+                    attr: attr_checkpoint.written_range(proc),
+                    value,
+                    source_start: attr_start,
+                    source_end: proc.read_len(),
+                    quoted: typ == AttrType::Quoted,
+                }, kind));
+            };
+        };
         if erase_attr {
             attr_checkpoint.erase_written(proc);
         } else {
+            if let Some((code, kind)) = pending_attr_code.take() {
+                minify_attr(proc, cfg, code, kind, true);
+            };
+            pending_attr_code = attr_code;
             last_attr_type = Some(typ);
         };
     };
+    if let Some((code, kind)) = pending_attr_code {
+        minify_attr(proc, cfg, code, kind, false);
+    };
 
     // TODO Self closing does not actually close for HTML elements, but might close for foreign elements.
     // See spec for more details.
@@ -199,7 +419,7 @@ pub fn process_tag(
                 proc.write_slice(b"/>");
             };
         };
-        return Ok(MaybeClosingTag(None));
+        return Ok(ProcessedTag::closing_tag(MaybeClosingTag(None)));
     };
 
     let child_ns = if proc[tag_name].eq(b"svg") {
@@ -208,29 +428,83 @@ pub fn process_tag(
         ns
     };
 
-    let mut closing_tag_omitted = false;
+    let mut content = ProcessedContent {
+        closing_tag_omitted: false,
+        ended_in_plaintext: false,
+        suspended: None,
+    };
+    proc.push_open_element(tag_name);
     match tag_type {
-        TagType::ScriptData => process_script(proc, cfg, false)?,
-        TagType::ScriptJs => process_script(proc, cfg, true)?,
+        TagType::ScriptData => process_script(proc, cfg, None)?,
+        TagType::ScriptJs => process_script(proc, cfg, Some(CodeKind::Js))?,
+        TagType::ScriptJsModule => process_script(proc, cfg, Some(CodeKind::JsModule))?,
+        TagType::ScriptJson => process_script(proc, cfg, Some(CodeKind::Json))?,
         TagType::Style => process_style(proc, cfg)?,
-        _ => closing_tag_omitted = process_content(proc, cfg, child_ns, Some(tag_name), descendant_of_pre)?.closing_tag_omitted,
+        TagType::RcData(end) => process_rcdata(proc, cfg, end, tag_name, preserve_whitespace),
+        TagType::RawText(end) => process_rawtext(proc, end),
+        TagType::PlainText => {
+            // There is no end tag; everything until the end of the code is text.
+            process_plaintext(proc);
+            content.ended_in_plaintext = true;
+        }
+        _ => content = process_content(proc, cfg, child_ns, Some(tag_name), preserve_whitespace)?,
+    };
+    process_tag_end(proc, cfg, parent, tag_name, tag_start, content)
+}
+
+// Process what's after the content of the element with the written `tag_name` that started at `tag_start`, which is
+// also where processing is resumed if it was suspended in the content.
+pub fn process_tag_end(
+    proc: &mut Processor,
+    cfg: &Cfg,
+    parent: Option<ProcessorRange>,
+    tag_name: ProcessorRange,
+    tag_start: usize,
+    content: ProcessedContent,
+) -> ProcessingResult<ProcessedTag> {
+    if let Some(mut suspended) = content.suspended {
+        suspended.push(Frame::TagEnd { parent, tag_name, tag_start });
+        return Ok(ProcessedTag::suspended(suspended));
+    };
+    proc.pop_open_element();
+    if content.ended_in_plaintext {
+        // The code ended inside this or a descendant `<plaintext>`, so this element can't have an end tag.
+        return Ok(ProcessedTag::ended_in_plaintext());
     };
 
     let can_omit_closing_tag = can_omit_as_last_node(proc, parent, tag_name);
-    if closing_tag_omitted || proc.at_end() && can_omit_closing_tag {
-        return Ok(MaybeClosingTag(None));
+    if content.closing_tag_omitted || proc.at_end() && can_omit_closing_tag {
+        return Ok(ProcessedTag::closing_tag(MaybeClosingTag(None)));
+    };
+    if proc.at_end() && proc.is_lenient() {
+        // Browsers implicitly close all open elements at the end.
+        proc.warn_at(tag_start, ErrorType::UnclosedElement(unsafe { String::from_utf8_unchecked(proc[tag_name].to_vec()) }));
+        return Ok(ProcessedTag::closing_tag(MaybeClosingTag(None)));
     };
 
     let closing_tag_checkpoint = ReadCheckpoint::new(proc);
-    proc.m(IsSeq(b"</"), Discard).require("closing tag")?;
-    let closing_tag = proc.m(WhileInLookup(TAG_NAME_CHAR), Discard).require("closing tag name")?;
-    proc.make_lowercase(closing_tag);
+    let closing_tag_start = proc.read_len();
+    proc.m(IsSeq(b"</"), Discard).require("closing tag", "element", tag_start)?;
+    let (closing_tag, has_template) = match_name(proc, cfg, TAG_NAME_CHAR, Discard);
+    closing_tag.require("closing tag name", "closing tag", closing_tag_start)?;
+    if !has_template {
+        proc.make_lowercase(closing_tag);
+    };
 
     // We need to check closing tag matches as otherwise when we later write closing tag, it might be longer than source closing tag and cause source to be overwritten.
     if proc[closing_tag] != proc[tag_name] {
         if can_omit_closing_tag {
             closing_tag_checkpoint.restore(proc);
-            Ok(MaybeClosingTag(None))
+            Ok(ProcessedTag::closing_tag(MaybeClosingTag(None)))
+        } else if proc.is_lenient() {
+            // The closing tag is for an ancestor, which implicitly closes this element. Don't write a closing tag for
+            // this element as it wasn't in the source, and writing it could overwrite unread source code.
+            closing_tag_checkpoint.restore(proc);
+            proc.warn(ErrorType::ClosingTagMismatch {
+                expected: unsafe { String::from_utf8_unchecked(proc[tag_name].to_vec()) },
+                got: unsafe { String::from_utf8_unchecked(proc[closing_tag].to_vec()) },
+            });
+            Ok(ProcessedTag::closing_tag(MaybeClosingTag(None)))
         } else {
             Err(ErrorType::ClosingTagMismatch {
                 expected: unsafe { String::from_utf8_unchecked(proc[tag_name].to_vec()) },
@@ -239,7 +513,16 @@ pub fn process_tag(
         }
     } else {
         proc.m(WhileInLookup(WHITESPACE), Discard);
-        proc.m(IsChar(b'>'), Discard).require("closing tag end")?;
-        Ok(MaybeClosingTag(Some(tag_name)))
+        if proc.m(IsChar(b'>'), Discard).empty() {
+            let err = ErrorType::NotFound { expected: "closing tag end", construct: "closing tag", start: closing_tag_start };
+            if !proc.is_lenient() {
+                return Err(err);
+            };
+            // Ignore anything else in the closing tag, like attributes.
+            proc.warn(err);
+            proc.m(WhileNotChar(b'>'), Discard);
+            proc.m(IsChar(b'>'), Discard);
+        };
+        Ok(ProcessedTag::closing_tag(MaybeClosingTag(Some(tag_name))))
     }
 }
@@ -0,0 +1,61 @@
+use crate::cfg::Cfg;
+use crate::gen::codepoints::Lookup;
+use crate::proc::checkpoint::{ReadCheckpoint, WriteCheckpoint};
+use crate::proc::MatchAction;
+use crate::proc::MatchAction::*;
+use crate::proc::MatchMode::*;
+use crate::proc::Processor;
+use crate::proc::range::ProcessorRange;
+
+// Returns the length of the template starting `offset` characters from the next character, including its delimiters.
+// Templates without a closing delimiter are not considered templates.
+pub fn template_len(proc: &Processor, cfg: &Cfg, offset: usize) -> Option<usize> {
+    let (i, (open, close)) = cfg.template_delimiters
+        .iter()
+        .enumerate()
+        .find(|(_, (open, _))| proc.peek_many(offset, open.len()).filter(|s| *s == &open[..]).is_some())?;
+    let end = proc.find_seq(i, offset + open.len(), close)?;
+    Some(end + close.len() - offset)
+}
+
+// Templates are copied as is, as their contents and what they will render to are unknown.
+#[inline(always)]
+pub fn process_template(proc: &mut Processor, cfg: &Cfg) -> () {
+    let len = template_len(proc, cfg, 0).unwrap();
+    proc.accept_amount_expect(len);
+}
+
+// Matches a name made of characters in `lookup` and any templates, e.g. `h{{ level }}`. Also returns whether any
+// templates were matched, as such names must not be modified.
+pub fn match_name(proc: &mut Processor, cfg: &Cfg, lookup: &'static Lookup, action: MatchAction) -> (ProcessorRange, bool) {
+    if cfg.template_delimiters.is_empty() {
+        return (proc.m(WhileInLookup(lookup), action), false);
+    };
+
+    let read_checkpoint = ReadCheckpoint::new(proc);
+    let write_checkpoint = WriteCheckpoint::new(proc);
+    let mut has_template = false;
+    loop {
+        // Check for a template first, as delimiters could contain characters in `lookup`.
+        let len = match template_len(proc, cfg, 0) {
+            Some(len) => {
+                has_template = true;
+                len
+            }
+            None => match proc.peek(0) {
+                Some(c) if lookup[c] => 1,
+                _ => break,
+            },
+        };
+        match action {
+            Keep => proc.accept_amount_expect(len),
+            Discard => proc.skip_amount_expect(len),
+            MatchOnly => unreachable!(),
+        };
+    };
+    let range = match action {
+        Keep => write_checkpoint.written_range(proc),
+        _ => read_checkpoint.read_range(proc),
+    };
+    (range, has_template)
+}